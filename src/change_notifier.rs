@@ -0,0 +1,300 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use super::pending_changes::PendingChanges;
+
+/* Keeps the callbacks interested in the changes of a model
+   and calls them with (position, removed, added),
+   the same arguments as GListModel's `items-changed`.
+
+   A notifying model owns one, hands it out through
+   `ListModel::get_change_notifier()` and calls `emit()`
+   after each mutation, once the new items can be read.
+
+   Anyone can connect to it: the ListModelGObject bound to gtk,
+   adapters built on the model, or plain Rust code
+   (eg: a counter in a status bar) through `ListModel::connect_items_changed()`.
+   All of them get the same emissions, in the order they connected.
+
+   `emit_item_updated()` tells that an item changed in place.
+   Subscribers connected `with_item_updated` hear it as such,
+   eg: to refresh a row instead of recreating it,
+   the others as the replacement (position, 1, 1).
+
+   While frozen, emissions are held back and merged,
+   to be emitted as few as possible on the last `thaw()`,
+   items updated meanwhile as replacements.
+   Clones share the same callbacks and freeze state. */
+#[derive(Clone)]
+pub struct ChangeNotifier {
+    state: Rc<RefCell<NotifierState>>
+}
+
+/* Identifies a callback connected to a ChangeNotifier. */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct HandlerId(usize);
+
+type Callback = Rc<dyn Fn(u32, u32, u32)>;
+type ItemUpdatedCallback = Rc<dyn Fn(u32)>;
+
+struct Connection {
+    id: usize,
+    items_changed: Callback,
+    item_updated: Option<ItemUpdatedCallback>
+}
+
+struct NotifierState {
+    next_id: usize,
+    connections: Vec<Connection>,
+    freeze_count: usize,
+    pending_changes: PendingChanges
+}
+
+impl ChangeNotifier {
+
+    pub fn new() -> Self {
+        ChangeNotifier {
+            state: Rc::new(RefCell::new(NotifierState {
+                next_id: 0,
+                connections: Vec::new(),
+                freeze_count: 0,
+                pending_changes: PendingChanges::new()
+            }))
+        }
+    }
+
+    pub fn emit(&self, position: u32, removed: u32, added: u32) {
+        if removed == 0 && added == 0 {
+            return;
+        }
+        {
+            let mut state = self.state.borrow_mut();
+            if state.freeze_count > 0 {
+                state.pending_changes.add(position, removed, added);
+                return;
+            }
+        }
+        /* Callbacks may read the model, connect or disconnect,
+           so they are called without holding the borrow. */
+        let callbacks: Vec<_> = self.state.borrow().connections.iter()
+            .map(|connection| (connection.id, connection.items_changed.clone()))
+            .collect();
+        for (id, callback) in callbacks {
+            if self.is_connected(id) {
+                callback(position, removed, added);
+            }
+        }
+    }
+
+    /* The item at `position` changed in place, once it can be read.
+       While frozen, it is held back as the replacement (position, 1, 1). */
+    pub fn emit_item_updated(&self, position: u32) {
+        {
+            let mut state = self.state.borrow_mut();
+            if state.freeze_count > 0 {
+                state.pending_changes.add(position, 1, 1);
+                return;
+            }
+        }
+        let callbacks: Vec<_> = self.state.borrow().connections.iter()
+            .map(|connection| (connection.id, connection.items_changed.clone(), connection.item_updated.clone()))
+            .collect();
+        for (id, items_changed, item_updated) in callbacks {
+            if !self.is_connected(id) {
+                continue;
+            }
+            match item_updated {
+                Some(item_updated) => item_updated(position),
+                None => items_changed(position, 1, 1)
+            }
+        }
+    }
+
+    /* Holds back emissions until as many `thaw()`s are made. */
+    pub fn freeze(&self) {
+        self.state.borrow_mut().freeze_count += 1;
+    }
+
+    /* The last thaw emits the changes made while frozen.
+       Overlapping and adjacent changes are merged,
+       changes apart from each other are emitted separately, front to back. */
+    pub fn thaw(&self) {
+        let changes = {
+            let mut state = self.state.borrow_mut();
+            assert!(state.freeze_count > 0, "ChangeNotifier::thaw() without freeze()");
+            state.freeze_count -= 1;
+            if state.freeze_count > 0 {
+                return;
+            }
+            state.pending_changes.take()
+        };
+        for (position, removed, added) in changes {
+            self.emit(position, removed, added);
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.state.borrow().freeze_count > 0
+    }
+
+    /* Runs `f` frozen. Thaws even if `f` panics. */
+    pub fn batch<R, F>(&self, f: F) -> R
+    where F: FnOnce() -> R {
+        struct ThawOnDrop<'a>(&'a ChangeNotifier);
+        impl<'a> Drop for ThawOnDrop<'a> {
+            fn drop(&mut self) {
+                self.0.thaw();
+            }
+        }
+        self.freeze();
+        let _thaw_on_drop = ThawOnDrop(self);
+        f()
+    }
+
+    /* `callback(position, removed, added)` is called after each change,
+       once the changed items can be read.
+       Keep the returned id to disconnect it. */
+    pub fn connect<F>(&self, callback: F) -> HandlerId
+    where F: Fn(u32, u32, u32) + 'static {
+        self.add_connection(Rc::new(callback), None)
+    }
+
+    /* Like connect(), with `item_updated(position)` called
+       for the items updated in place, instead of `items_changed`. */
+    pub fn connect_with_item_updated<F, U>(&self, items_changed: F, item_updated: U) -> HandlerId
+    where F: Fn(u32, u32, u32) + 'static, U: Fn(u32) + 'static {
+        self.add_connection(Rc::new(items_changed), Some(Rc::new(item_updated)))
+    }
+
+    fn add_connection(&self, items_changed: Callback, item_updated: Option<ItemUpdatedCallback>) -> HandlerId {
+        let mut state = self.state.borrow_mut();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.connections.push(Connection { id, items_changed, item_updated });
+        HandlerId(id)
+    }
+
+    /* A disconnected callback isn't called anymore,
+       even by an emission in progress. */
+    pub fn disconnect(&self, handler_id: HandlerId) {
+        self.state.borrow_mut().connections.retain(|connection| connection.id != handler_id.0);
+    }
+
+    /* How many callbacks are connected, eg: to check that an adapter let go. */
+    pub fn handler_count(&self) -> usize {
+        self.state.borrow().connections.len()
+    }
+
+    fn is_connected(&self, handler_id: usize) -> bool {
+        self.state.borrow().connections.iter().any(|connection| connection.id == handler_id)
+    }
+}
+
+impl Default for ChangeNotifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use super::ChangeNotifier;
+
+    #[test]
+    fn connected_callbacks_are_called() {
+        let emissions = Rc::new(RefCell::new(Vec::new()));
+        let notifier = ChangeNotifier::new();
+        let emissions_clone = emissions.clone();
+        let handler_id = notifier.connect(move |position, removed, added| {
+            emissions_clone.borrow_mut().push((position, removed, added));
+        });
+        notifier.emit(1, 2, 3);
+        notifier.clone().emit(4, 0, 1);
+        notifier.disconnect(handler_id);
+        notifier.emit(5, 1, 0);
+        assert_eq!(*emissions.borrow(), vec![(1, 2, 3), (4, 0, 1)]);
+    }
+
+    #[test]
+    /* Changes made while frozen come out merged on the last thaw. */
+    fn frozen_changes_are_merged() {
+        let emissions = Rc::new(RefCell::new(Vec::new()));
+        let notifier = ChangeNotifier::new();
+        let emissions_clone = emissions.clone();
+        notifier.connect(move |position, removed, added| {
+            emissions_clone.borrow_mut().push((position, removed, added));
+        });
+        notifier.freeze();
+        notifier.emit(3, 0, 1);
+        notifier.batch(|| {
+            notifier.emit(4, 0, 1);
+            notifier.emit(0, 1, 0);
+        });
+        assert!(notifier.is_frozen());
+        assert!(emissions.borrow().is_empty());
+        notifier.thaw();
+        assert_eq!(*emissions.borrow(), vec![(0, 1, 0), (2, 0, 2)]);
+    }
+
+    #[test]
+    /* A callback disconnecting another one during an emission
+       keeps it from being called. */
+    fn disconnect_during_emission() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let notifier = ChangeNotifier::new();
+        let second_id = Rc::new(RefCell::new(None));
+        let (notifier_clone, second_id_clone, calls_clone) = (notifier.clone(), second_id.clone(), calls.clone());
+        notifier.connect(move |_, _, _| {
+            calls_clone.borrow_mut().push("first");
+            if let Some(id) = second_id_clone.borrow_mut().take() {
+                notifier_clone.disconnect(id);
+            }
+        });
+        let calls_clone = calls.clone();
+        *second_id.borrow_mut() = Some(notifier.connect(move |_, _, _| calls_clone.borrow_mut().push("second")));
+        notifier.emit(0, 0, 1);
+        notifier.emit(0, 0, 1);
+        assert_eq!(*calls.borrow(), vec!["first", "first"]);
+    }
+
+    #[test]
+    /* Updated items reach the subscribers which asked for them as such,
+       the others and frozen notifiers get a replacement. */
+    fn item_updates_fall_back_to_replacements() {
+        let emissions = Rc::new(RefCell::new(Vec::new()));
+        let notifier = ChangeNotifier::new();
+        let emissions_clone = emissions.clone();
+        notifier.connect(move |position, removed, added| {
+            emissions_clone.borrow_mut().push(format!("changed {} {} {}", position, removed, added));
+        });
+        let (emissions_changed, emissions_updated) = (emissions.clone(), emissions.clone());
+        notifier.connect_with_item_updated(
+            move |position, removed, added| {
+                emissions_changed.borrow_mut().push(format!("changed' {} {} {}", position, removed, added));
+            },
+            move |position| emissions_updated.borrow_mut().push(format!("updated' {}", position))
+        );
+        notifier.emit_item_updated(2);
+        notifier.batch(|| {
+            notifier.emit_item_updated(1);
+            notifier.emit(1, 0, 1);
+        });
+        assert_eq!(*emissions.borrow(), vec![
+            "changed 2 1 1", "updated' 2",
+            "changed 1 1 2", "changed' 1 1 2"
+        ]);
+    }
+
+    #[test]
+    /* Nothing changed, nothing to tell. */
+    fn empty_changes_are_not_emitted() {
+        let emitted = Rc::new(RefCell::new(false));
+        let notifier = ChangeNotifier::new();
+        let emitted_clone = emitted.clone();
+        notifier.connect(move |_, _, _| { *emitted_clone.borrow_mut() = true; });
+        notifier.emit(3, 0, 0);
+        assert!(!*emitted.borrow());
+    }
+}
@@ -0,0 +1,216 @@
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use super::public_interface::ListModel;
+use super::change_notifier::{ChangeNotifier, HandlerId};
+
+/* Presents several child models back to back, as a single list.
+   Changes of a notifying child are reported at the child's
+   offset in the combined list.
+
+   `locate()` tells which child a position belongs to,
+   eg: for a header func drawing section titles. Clones share the same state. */
+pub struct ConcatListModel<T> {
+    inner: Rc<ConcatInner<T>>
+}
+
+struct ConcatInner<T> {
+    children: RefCell<Vec<Child<T>>>,
+    next_child_id: Cell<usize>,
+    change_notifier: ChangeNotifier
+}
+
+struct Child<T> {
+    // stays the same when children in front are removed
+    id: usize,
+    model: Rc<dyn ListModel<T>>,
+    handler_id: Option<HandlerId>
+}
+
+impl<T> Child<T> {
+    fn disconnect(&self) {
+        if let (Some(handler_id), Some(child_notifier)) = (self.handler_id, self.model.get_change_notifier()) {
+            child_notifier.disconnect(handler_id);
+        }
+    }
+}
+
+impl<T> ConcatListModel<T> where T: 'static {
+
+    pub fn new() -> Self {
+        ConcatListModel {
+            inner: Rc::new(ConcatInner {
+                children: RefCell::new(Vec::new()),
+                next_child_id: Cell::new(0),
+                change_notifier: ChangeNotifier::new()
+            })
+        }
+    }
+
+    pub fn get_n_children(&self) -> usize {
+        self.inner.children.borrow().len()
+    }
+
+    /* Adds `model` after the current children and returns its child index. */
+    pub fn append<LM>(&self, model: LM) -> usize
+    where LM: ListModel<T> + 'static {
+        let id = self.inner.next_child_id.get();
+        self.inner.next_child_id.set(id + 1);
+        let model: Rc<dyn ListModel<T>> = Rc::new(model);
+        let handler_id = model.get_change_notifier().map(|child_notifier| {
+            let weak_inner = Rc::downgrade(&self.inner);
+            child_notifier.connect(move |position, removed, added| {
+                if let Some(inner) = weak_inner.upgrade() {
+                    inner.child_items_changed(id, position, removed, added);
+                }
+            })
+        });
+        let (child_index, offset, added) = {
+            let mut children = self.inner.children.borrow_mut();
+            let offset = children.iter().map(|child| child.model.get_n_items()).sum();
+            children.push(Child { id, model: model.clone(), handler_id });
+            (children.len() - 1, offset, model.get_n_items())
+        };
+        self.inner.change_notifier.emit(offset, 0, added);
+        child_index
+    }
+
+    /* Removes the child at `child_index` along with its items. */
+    pub fn remove_child(&self, child_index: usize) {
+        let (offset, removed) = {
+            let mut children = self.inner.children.borrow_mut();
+            let offset = children[..child_index].iter().map(|child| child.model.get_n_items()).sum();
+            let child = children.remove(child_index);
+            child.disconnect();
+            (offset, child.model.get_n_items())
+        };
+        self.inner.change_notifier.emit(offset, removed, 0);
+    }
+
+    /* The child index and the position within that child
+       for a position in the combined list. */
+    pub fn locate(&self, position: u32) -> Option<(usize, u32)> {
+        self.inner.locate(position).map(|(child_index, _, local_position)| (child_index, local_position))
+    }
+
+    /* Position in the combined list of the first item of a child. */
+    pub fn get_child_offset(&self, child_index: usize) -> u32 {
+        self.inner.children.borrow()[..child_index].iter().map(|child| child.model.get_n_items()).sum()
+    }
+}
+
+impl<T> ConcatInner<T> {
+
+    fn locate(&self, position: u32) -> Option<(usize, Rc<dyn ListModel<T>>, u32)> {
+        let mut offset = 0;
+        for (child_index, child) in self.children.borrow().iter().enumerate() {
+            let n_items = child.model.get_n_items();
+            if position < offset + n_items {
+                return Some((child_index, child.model.clone(), position - offset));
+            }
+            offset += n_items;
+        }
+        None
+    }
+
+    fn child_items_changed(&self, child_id: usize, position: u32, removed: u32, added: u32) {
+        let offset = {
+            let children = self.children.borrow();
+            let offset: u32 = children.iter()
+                .take_while(|child| child.id != child_id)
+                .map(|child| child.model.get_n_items())
+                .sum();
+            offset
+        };
+        self.change_notifier.emit(offset + position, removed, added);
+    }
+}
+
+impl<T> Drop for ConcatInner<T> {
+    fn drop(&mut self) {
+        for child in self.children.borrow().iter() {
+            child.disconnect();
+        }
+    }
+}
+
+impl<T> Clone for ConcatListModel<T> {
+    fn clone(&self) -> Self {
+        ConcatListModel { inner: self.inner.clone() }
+    }
+}
+
+impl<T> Default for ConcatListModel<T> where T: 'static {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ListModel<T> for ConcatListModel<T> {
+    fn get_n_items(&self) -> u32 {
+        self.inner.children.borrow().iter().map(|child| child.model.get_n_items()).sum()
+    }
+
+    fn get_item(&self, index: u32) -> T {
+        match self.inner.locate(index) {
+            Some((_, model, local_index)) => model.get_item(local_index),
+            None => panic!("Index out of bounds: ConcatListModel")
+        }
+    }
+
+    fn try_get_item(&self, index: u32) -> Option<T> {
+        let (_, model, local_index) = self.inner.locate(index)?;
+        model.try_get_item(local_index)
+    }
+
+    fn get_change_notifier(&self) -> Option<&ChangeNotifier> {
+        Some(&self.inner.change_notifier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::ConcatListModel;
+    use super::super::vec_list_model::VecListModel;
+    use super::super::test_helpers::{record_emissions, list_items as items};
+
+    #[test]
+    fn children_are_joined() {
+        let concat_model = ConcatListModel::new();
+        let emissions = record_emissions(&concat_model);
+        concat_model.append(VecListModel::from(vec!["pinned"]));
+        concat_model.append(VecListModel::<&str>::new());
+        concat_model.append(VecListModel::from(vec!["all 1", "all 2"]));
+        assert_eq!(items(&concat_model), vec!["pinned", "all 1", "all 2"]);
+        assert_eq!(*emissions.borrow(), vec![(0, 0, 1), (1, 0, 2)]);
+
+        assert_eq!(concat_model.locate(0), Some((0, 0)));
+        assert_eq!(concat_model.locate(2), Some((2, 1)));
+        assert_eq!(concat_model.locate(3), None);
+        assert_eq!(concat_model.get_child_offset(2), 1);
+    }
+
+    #[test]
+    /* Child changes are moved by the size of the children in front. */
+    fn child_changes_are_offset() {
+        let pinned = VecListModel::from(vec![1, 2]);
+        let recent = VecListModel::from(vec![3]);
+        let concat_model = ConcatListModel::new();
+        concat_model.append(pinned.clone());
+        concat_model.append(recent.clone());
+        let emissions = record_emissions(&concat_model);
+
+        recent.push(4);
+        pinned.remove(0);
+        recent.insert(0, 5);
+        assert_eq!(items(&concat_model), vec![2, 5, 3, 4]);
+        assert_eq!(*emissions.borrow(), vec![(3, 0, 1), (0, 1, 0), (1, 0, 1)]);
+
+        emissions.borrow_mut().clear();
+        concat_model.remove_child(0);
+        recent.push(6);
+        pinned.push(7);
+        assert_eq!(items(&concat_model), vec![5, 3, 4, 6]);
+        assert_eq!(*emissions.borrow(), vec![(0, 1, 0), (3, 0, 1)]);
+    }
+}
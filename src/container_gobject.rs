@@ -2,6 +2,7 @@ extern crate libc;
 extern crate gobject_sys as gobject_ffi;
 extern crate glib_sys as glib_ffi;
 extern crate gtk_sys as gtk_ffi;
+extern crate glib;
 
 use std::ptr;
 use std::mem;
@@ -9,7 +10,9 @@ use std::ffi::CString;
 use std::collections::hash_map::HashMap;
 use std::any::TypeId;
 use self::libc::c_void;
+use self::glib::object::IsA;
 use super::GObjectPtrWrapper;
+use super::panic_guard;
 
 /* To make any rust type returnable
    from Gtk/GObject related interfaces,
@@ -60,6 +63,24 @@ where T: 'static {
         &self.value
     }
 
+    /* None unless `object` is a ContainerGObject of this very T,
+       eg: an item handed out by a GListModel. */
+    pub fn from_object<O>(object: &O) -> Option<GObjectPtrWrapper<Self>>
+    where O: IsA<glib::Object> {
+        unsafe {
+            let gobj_ptr: *mut gobject_ffi::GObject = object.to_glib_none().0;
+            let is_self = gobject_ffi::g_type_check_instance_is_a(
+                gobj_ptr as *mut gobject_ffi::GTypeInstance,
+                Self::get_type()
+            );
+            if is_self == glib_ffi::GFALSE {
+                return None;
+            }
+            gobject_ffi::g_object_ref(gobj_ptr as *mut _);
+            Some(GObjectPtrWrapper::<Self>(gobj_ptr as *mut Self))
+        }
+    }
+
     extern "C" fn class_init(klass_ptr: *mut c_void, _data: *mut c_void) {
         let klass_ptr = klass_ptr as *mut gobject_ffi::GObjectClass;
         unsafe { (*klass_ptr).dispose = Some(Self::dispose); }
@@ -68,7 +89,8 @@ where T: 'static {
     extern "C" fn dispose(gobj_ptr: *mut gobject_ffi::GObject) {
         unsafe {
             let self_ptr = gobj_ptr as *mut Self;
-            ptr::drop_in_place(&mut (*self_ptr).value);
+            // a panicking Drop of value must not keep the parent from disposing
+            panic_guard::guard("ContainerGObject::dispose", || ptr::drop_in_place(&mut (*self_ptr).value));
             let parent_class: *mut gobject_ffi::GObjectClass = gobject_ffi::g_type_class_peek_parent(
                 gobject_ffi::g_type_class_peek_static(Self::get_type())
             ) as *mut _;
@@ -0,0 +1,191 @@
+use std::ops::Range;
+
+/* Beyond this many insertions plus deletions, the middle part
+   of the lists is treated as replaced as a whole.
+   Keeps the memory used for backtracking within (2 * MAX_EDIT_DISTANCE)^2 entries. */
+const MAX_EDIT_DISTANCE: usize = 512;
+
+/* A range of old items replaced by a range of new items.
+   Between two hunks, old and new items match. */
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Hunk {
+    pub old: Range<usize>,
+    pub new: Range<usize>
+}
+
+/* Pairs of (old index, new index) of a longest common subsequence
+   of `old` and `new`, ascending. Myers' O((N+M)D) algorithm,
+   run on what remains after the common prefix and suffix. */
+pub fn matching_pairs<K: Eq>(old: &[K], new: &[K]) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new.iter()).take_while(|&(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|&(a, b)| a == b).count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|index| (index, index)).collect();
+    if let Some(middle_pairs) = myers(old_middle, new_middle) {
+        pairs.extend(middle_pairs.into_iter().map(|(x, y)| (x + prefix, y + prefix)));
+    }
+    let (old_suffix_start, new_suffix_start) = (old.len() - suffix, new.len() - suffix);
+    pairs.extend((0..suffix).map(|index| (old_suffix_start + index, new_suffix_start + index)));
+    pairs
+}
+
+/* The ranges between matching pairs. */
+pub fn hunks(pairs: &[(usize, usize)], old_len: usize, new_len: usize) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    let (mut old_position, mut new_position) = (0, 0);
+    for &(old_index, new_index) in pairs.iter().chain(Some(&(old_len, new_len))) {
+        if old_index > old_position || new_index > new_position {
+            hunks.push(Hunk {
+                old: old_position..old_index,
+                new: new_position..new_index
+            });
+        }
+        old_position = old_index + 1;
+        new_position = new_index + 1;
+    }
+    hunks
+}
+
+/* None when more than MAX_EDIT_DISTANCE edits are needed. */
+fn myers<K: Eq>(old: &[K], new: &[K]) -> Option<Vec<(usize, usize)>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max_d = (n + m).min(MAX_EDIT_DISTANCE as isize);
+    // v[offset + k]: furthest x reached on diagonal k = x - y
+    let offset = max_d + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut found = false;
+    'search: for d in 0..max_d + 1 {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let index = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                found = true;
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+    if !found {
+        return None;
+    }
+
+    // walk back from the end, collecting the diagonal moves
+    let mut pairs = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..trace.len()).rev() {
+        let v = &trace[d];
+        let d = d as isize;
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let previous_x = v[(offset + previous_k) as usize];
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            pairs.push((x as usize, y as usize));
+        }
+        if d > 0 {
+            x = previous_x;
+            y = previous_y;
+        }
+    }
+    pairs.reverse();
+    Some(pairs)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{matching_pairs, hunks, Hunk};
+
+    /* Length of a longest common subsequence, the slow way. */
+    fn lcs_length(old: &[u8], new: &[u8]) -> usize {
+        let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in 0..old.len() {
+            for j in 0..new.len() {
+                lengths[i + 1][j + 1] = if old[i] == new[j] {
+                    lengths[i][j] + 1
+                } else {
+                    lengths[i][j + 1].max(lengths[i + 1][j])
+                };
+            }
+        }
+        lengths[old.len()][new.len()]
+    }
+
+    fn check(old: &[u8], new: &[u8]) {
+        let pairs = matching_pairs(old, new);
+        assert_eq!(pairs.len(), lcs_length(old, new), "{:?} -> {:?}", old, new);
+        for window in pairs.windows(2) {
+            assert!(window[0].0 < window[1].0 && window[0].1 < window[1].1);
+        }
+        for &(x, y) in pairs.iter() {
+            assert_eq!(old[x], new[y]);
+        }
+    }
+
+    #[test]
+    fn finds_longest_common_subsequence() {
+        check(b"", b"");
+        check(b"abc", b"");
+        check(b"", b"abc");
+        check(b"abcabba", b"cbabac");
+        check(b"kitten", b"sitting");
+        check(b"abcdef", b"abcdef");
+        check(b"aaaa", b"aa");
+        check(b"xabcx", b"yabcy");
+    }
+
+    #[test]
+    /* Compared with the slow way over a few hundred short lists. */
+    fn matches_slow_lcs() {
+        let lists: Vec<Vec<u8>> = (0..3u32.pow(5)).map(|mut number| {
+            let length = (number % 6) as usize;
+            (0..length).map(|_| { let digit = (number % 3) as u8; number /= 3; b'a' + digit }).collect()
+        }).collect();
+        for old in lists.iter() {
+            for new in lists.iter() {
+                check(old, new);
+            }
+        }
+    }
+
+    #[test]
+    fn hunks_between_pairs() {
+        let pairs = matching_pairs(b"abxcd", b"zabcdy");
+        assert_eq!(hunks(&pairs, 5, 6), vec![
+            Hunk { old: 0..0, new: 0..1 },
+            Hunk { old: 2..3, new: 3..3 },
+            Hunk { old: 5..5, new: 5..6 }
+        ]);
+    }
+
+    #[test]
+    /* Past MAX_EDIT_DISTANCE the differing middle becomes a single hunk. */
+    fn large_edits_fall_back_to_one_hunk() {
+        let old: Vec<u32> = (0..2000).collect();
+        let new: Vec<u32> = (0..2000).map(|number| if number == 0 || number == 1999 { number } else { number + 5000 }).collect();
+        let pairs = matching_pairs(&old, &new);
+        assert_eq!(hunks(&pairs, 2000, 2000), vec![Hunk { old: 1..1999, new: 1..1999 }]);
+    }
+}
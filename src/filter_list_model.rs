@@ -0,0 +1,233 @@
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use super::public_interface::ListModel;
+use super::change_notifier::{ChangeNotifier, HandlerId};
+
+/* Shows only the items of a child model for which `filter` returns true.
+
+   GtkListBox ignores its filter func once a model is bound,
+   so filtering has to happen here, before ListModelGObject.
+
+   Changes of a notifying child are followed as they happen.
+   When the outcome of `filter` changes for some other reason,
+   call `refilter()`. Clones share the same state. */
+pub struct FilterListModel<T, LM, F>
+where LM: ListModel<T>, F: Fn(&T) -> bool {
+    inner: Rc<FilterInner<T, LM, F>>
+}
+
+struct FilterInner<T, LM, F>
+where LM: ListModel<T>, F: Fn(&T) -> bool {
+    child: LM,
+    filter: F,
+    // child indices of the items passing the filter, ascending
+    visible: RefCell<Vec<u32>>,
+    change_notifier: ChangeNotifier,
+    child_handler_id: Cell<Option<HandlerId>>,
+    phantom: PhantomData<T>
+}
+
+impl<T, LM, F> FilterListModel<T, LM, F>
+where T: 'static, LM: ListModel<T> + 'static, F: Fn(&T) -> bool + 'static {
+
+    pub fn new(child: LM, filter: F) -> Self {
+        let inner = Rc::new(FilterInner {
+            child,
+            filter,
+            visible: RefCell::new(Vec::new()),
+            change_notifier: ChangeNotifier::new(),
+            child_handler_id: Cell::new(None),
+            phantom: PhantomData
+        });
+        let visible = inner.filter_range(0, inner.child.get_n_items());
+        *inner.visible.borrow_mut() = visible;
+
+        if let Some(child_notifier) = inner.child.get_change_notifier() {
+            let weak_inner = Rc::downgrade(&inner);
+            let handler_id = child_notifier.connect(move |position, removed, added| {
+                if let Some(inner) = weak_inner.upgrade() {
+                    inner.child_items_changed(position, removed, added);
+                }
+            });
+            inner.child_handler_id.set(Some(handler_id));
+        }
+        FilterListModel { inner }
+    }
+
+    pub fn get_child(&self) -> &LM {
+        &self.inner.child
+    }
+
+    /* Runs the filter over every child item again
+       and reports each run of items which appeared or disappeared. */
+    pub fn refilter(&self) {
+        let new_visible = self.inner.filter_range(0, self.inner.child.get_n_items());
+        let old_visible = self.inner.visible.borrow().clone();
+        let (mut old_index, mut new_index) = (0, 0);
+        // position in the list as it is after the runs applied so far
+        let mut position = 0;
+        while old_index < old_visible.len() || new_index < new_visible.len() {
+            if old_index < old_visible.len() && new_index < new_visible.len()
+                && old_visible[old_index] == new_visible[new_index] {
+                old_index += 1;
+                new_index += 1;
+                position += 1;
+                continue;
+            }
+            let mut removed = 0;
+            let mut added = Vec::new();
+            while (old_index < old_visible.len() || new_index < new_visible.len())
+                && !(old_index < old_visible.len() && new_index < new_visible.len()
+                     && old_visible[old_index] == new_visible[new_index]) {
+                if new_index >= new_visible.len()
+                    || (old_index < old_visible.len() && old_visible[old_index] < new_visible[new_index]) {
+                    removed += 1;
+                    old_index += 1;
+                } else {
+                    added.push(new_visible[new_index]);
+                    new_index += 1;
+                }
+            }
+            let added_count = added.len();
+            self.inner.visible.borrow_mut().splice(position..position + removed, added);
+            self.inner.change_notifier.emit(position as u32, removed as u32, added_count as u32);
+            position += added_count;
+        }
+    }
+}
+
+impl<T, LM, F> FilterInner<T, LM, F>
+where LM: ListModel<T>, F: Fn(&T) -> bool {
+
+    /* Child indices in [start, end) whose items pass the filter. */
+    fn filter_range(&self, start: u32, end: u32) -> Vec<u32> {
+        (start..end).filter(|&index| {
+            self.child.try_get_item(index).is_some_and(|item| (self.filter)(&item))
+        }).collect()
+    }
+
+    fn child_items_changed(&self, position: u32, removed: u32, added: u32) {
+        let newly_visible = self.filter_range(position, position + added);
+        let (first, removed_count, added_count) = {
+            let mut visible = self.visible.borrow_mut();
+            let first = visible.iter().position(|&index| index >= position).unwrap_or(visible.len());
+            let end = visible.iter().position(|&index| index >= position + removed).unwrap_or(visible.len());
+            for index in visible[end..].iter_mut() {
+                *index = *index + added - removed;
+            }
+            let added_count = newly_visible.len();
+            visible.splice(first..end, newly_visible);
+            (first as u32, (end - first) as u32, added_count as u32)
+        };
+        self.change_notifier.emit(first, removed_count, added_count);
+    }
+}
+
+impl<T, LM, F> Drop for FilterInner<T, LM, F>
+where LM: ListModel<T>, F: Fn(&T) -> bool {
+    fn drop(&mut self) {
+        if let (Some(handler_id), Some(child_notifier)) = (self.child_handler_id.get(), self.child.get_change_notifier()) {
+            child_notifier.disconnect(handler_id);
+        }
+    }
+}
+
+impl<T, LM, F> Clone for FilterListModel<T, LM, F>
+where LM: ListModel<T>, F: Fn(&T) -> bool {
+    fn clone(&self) -> Self {
+        FilterListModel { inner: self.inner.clone() }
+    }
+}
+
+impl<T, LM, F> ListModel<T> for FilterListModel<T, LM, F>
+where LM: ListModel<T>, F: Fn(&T) -> bool {
+    fn get_n_items(&self) -> u32 {
+        self.inner.visible.borrow().len() as u32
+    }
+
+    fn get_item(&self, index: u32) -> T {
+        let child_index = self.inner.visible.borrow()[index as usize];
+        self.inner.child.get_item(child_index)
+    }
+
+    fn try_get_item(&self, index: u32) -> Option<T> {
+        let child_index = *self.inner.visible.borrow().get(index as usize)?;
+        self.inner.child.try_get_item(child_index)
+    }
+
+    fn get_change_notifier(&self) -> Option<&ChangeNotifier> {
+        Some(&self.inner.change_notifier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::rc::Rc;
+    use std::cell::Cell;
+    use super::FilterListModel;
+    use super::super::public_interface::ListModel;
+    use super::super::vec_list_model::VecListModel;
+    use super::super::test_helpers::{record_emissions, list_items as items};
+
+    #[test]
+    fn only_matching_items_are_shown() {
+        let filter_model = FilterListModel::new(VecListModel::from(vec![1, 2, 3, 4, 5, 6]), |&item: &i32| item % 2 == 0);
+        assert_eq!(items(&filter_model), vec![2, 4, 6]);
+        assert_eq!(filter_model.try_get_item(3), None);
+    }
+
+    #[test]
+    /* Only the runs of items whose visibility changed are reported. */
+    fn refilter_reports_changed_runs() {
+        let threshold = Rc::new(Cell::new(3));
+        let threshold_clone = threshold.clone();
+        let filter_model = FilterListModel::new(
+            VecListModel::from(vec![5, 1, 2, 7, 8, 3, 9]),
+            move |&item: &i32| item > threshold_clone.get()
+        );
+        assert_eq!(items(&filter_model), vec![5, 7, 8, 9]);
+        let emissions = record_emissions(&filter_model);
+
+        threshold.set(1);
+        filter_model.refilter();
+        assert_eq!(items(&filter_model), vec![5, 2, 7, 8, 3, 9]);
+        assert_eq!(*emissions.borrow(), vec![(1, 0, 1), (4, 0, 1)]);
+
+        emissions.borrow_mut().clear();
+        threshold.set(7);
+        filter_model.refilter();
+        assert_eq!(items(&filter_model), vec![8, 9]);
+        assert_eq!(*emissions.borrow(), vec![(0, 3, 0), (1, 1, 0)]);
+    }
+
+    #[test]
+    /* Changes of the child model are filtered and
+       translated into positions of the filtered list. */
+    fn follows_child_changes() {
+        let child = VecListModel::from(vec![2, 3, 4]);
+        let filter_model = FilterListModel::new(child.clone(), |&item: &i32| item % 2 == 0);
+        let emissions = record_emissions(&filter_model);
+
+        child.insert(1, 6);
+        child.insert(1, 5);
+        child.remove(0);
+        child.push(8);
+        assert_eq!(items(&filter_model), vec![6, 4, 8]);
+        assert_eq!(*emissions.borrow(), vec![(1, 0, 1), (0, 1, 0), (2, 0, 1)]);
+    }
+
+    #[test]
+    /* A dropped filter model stops listening to its child. */
+    fn disconnects_from_child_on_drop() {
+        let child = VecListModel::from(vec![1]);
+        let notifier = child.get_change_notifier().unwrap().clone();
+        {
+            let _filter_model = FilterListModel::new(child.clone(), |_: &i32| true);
+            assert_eq!(notifier.handler_count(), 1);
+        }
+        assert_eq!(notifier.handler_count(), 0);
+        child.push(2);
+    }
+}
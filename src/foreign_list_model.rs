@@ -0,0 +1,240 @@
+extern crate libc;
+extern crate gobject_sys as gobject_ffi;
+extern crate glib_sys as glib_ffi;
+extern crate gio_sys as gio_ffi;
+extern crate glib;
+
+use std::mem;
+use std::rc::Rc;
+use self::libc::c_ulong;
+use self::glib::translate::*;
+use self::glib::object::{Cast, IsA};
+use super::public_interface::ListModel;
+use super::container_gobject::ContainerGObject;
+use super::change_notifier::ChangeNotifier;
+use super::panic_guard;
+
+/* Presents a GListModel made on the C side, eg: a GListStore
+   or a model handed out by another library, as a Rust ListModel,
+   so it can go through the same adapters as the Rust models.
+
+   `convert` turns each item into a T, None when it can't,
+   which the adapters treat like a missing item.
+   The `items-changed` emissions of the foreign model
+   come out of its ChangeNotifier. Clones share the same state. */
+pub struct ForeignListModel<T> {
+    inner: Rc<ForeignInner<T>>
+}
+
+struct ForeignInner<T> {
+    // holds a reference
+    list_model_ptr: *mut gio_ffi::GListModel,
+    convert: Box<dyn Fn(glib::Object) -> Option<T>>,
+    change_notifier: ChangeNotifier,
+    signal_handler_id: c_ulong
+}
+
+impl<T> ForeignListModel<T> where T: 'static {
+
+    /* None if `list_model` doesn't implement GListModel. */
+    pub fn new<F>(list_model: &glib::Object, convert: F) -> Option<Self>
+    where F: Fn(glib::Object) -> Option<T> + 'static {
+        unsafe {
+            let gobj_ptr: *mut gobject_ffi::GObject = list_model.to_glib_none().0;
+            let is_list_model = gobject_ffi::g_type_check_instance_is_a(
+                gobj_ptr as *mut gobject_ffi::GTypeInstance,
+                gio_ffi::g_list_model_get_type()
+            );
+            if is_list_model == glib_ffi::GFALSE {
+                return None;
+            }
+            let list_model_ptr = gobject_ffi::g_object_ref(gobj_ptr as *mut _) as *mut gio_ffi::GListModel;
+
+            let change_notifier = ChangeNotifier::new();
+            // owned by the signal connection, freed when it's disconnected
+            let notifier_ptr = Box::into_raw(Box::new(change_notifier.clone()));
+            let callback: unsafe extern "C" fn() = mem::transmute(
+                forward_items_changed as extern "C" fn(_, _, _, _, _)
+            );
+            let signal_handler_id = gobject_ffi::g_signal_connect_data(
+                list_model_ptr as *mut _,
+                b"items-changed\0".as_ptr() as *const _,
+                Some(callback),
+                notifier_ptr as glib_ffi::gpointer,
+                Some(free_notifier),
+                gobject_ffi::GConnectFlags::empty()
+            );
+
+            Some(ForeignListModel {
+                inner: Rc::new(ForeignInner {
+                    list_model_ptr,
+                    convert: Box::new(convert),
+                    change_notifier,
+                    signal_handler_id
+                })
+            })
+        }
+    }
+
+    /* For models of GObjects, eg: a GListStore of gtk widgets.
+       Items of another type than T are None. */
+    pub fn with_object_items(list_model: &glib::Object) -> Option<Self>
+    where T: IsA<glib::Object> {
+        Self::new(list_model, |item| item.downcast::<T>().ok())
+    }
+
+    /* For models of ContainerGObject<T>, eg: a ListModelGObject
+       passed through C code. Items are cloned out of their container,
+       items of another type are None. */
+    pub fn with_container_items(list_model: &glib::Object) -> Option<Self>
+    where T: Clone {
+        Self::new(list_model, |item| {
+            ContainerGObject::<T>::from_object(&item).map(|container_gobj| container_gobj.get().clone())
+        })
+    }
+}
+
+extern "C" fn forward_items_changed(_list_model_ptr: *mut gio_ffi::GListModel,
+                                    position: u32, removed: u32, added: u32,
+                                    user_data: glib_ffi::gpointer) {
+    let change_notifier = user_data as *const ChangeNotifier;
+    panic_guard::guard("ForeignListModel items-changed", || unsafe {
+        (*change_notifier).emit(position, removed, added);
+    });
+}
+
+extern "C" fn free_notifier(user_data: glib_ffi::gpointer, _closure: *mut gobject_ffi::GClosure) {
+    panic_guard::guard("ForeignListModel free_notifier", || unsafe {
+        drop(Box::from_raw(user_data as *mut ChangeNotifier));
+    });
+}
+
+impl<T> Drop for ForeignInner<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gobject_ffi::g_signal_handler_disconnect(self.list_model_ptr as *mut _, self.signal_handler_id);
+            gobject_ffi::g_object_unref(self.list_model_ptr as *mut _);
+        }
+    }
+}
+
+impl<T> Clone for ForeignListModel<T> {
+    fn clone(&self) -> Self {
+        ForeignListModel { inner: self.inner.clone() }
+    }
+}
+
+impl<T> ListModel<T> for ForeignListModel<T> {
+    fn get_n_items(&self) -> u32 {
+        unsafe { gio_ffi::g_list_model_get_n_items(self.inner.list_model_ptr) }
+    }
+
+    fn get_item(&self, index: u32) -> T {
+        match self.try_get_item(index) {
+            Some(item) => item,
+            None => panic!("No item of the expected type at index: ForeignListModel")
+        }
+    }
+
+    fn try_get_item(&self, index: u32) -> Option<T> {
+        let item: Option<glib::Object> = unsafe {
+            from_glib_full(gio_ffi::g_list_model_get_item(self.inner.list_model_ptr, index))
+        };
+        item.and_then(|item| (self.inner.convert)(item))
+    }
+
+    fn get_change_notifier(&self) -> Option<&ChangeNotifier> {
+        Some(&self.inner.change_notifier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    extern crate gobject_sys as gobject_ffi;
+    extern crate glib_sys as glib_ffi;
+    extern crate gio_sys as gio_ffi;
+    extern crate glib;
+
+    use std::ptr;
+    use self::glib::translate::*;
+    use super::ForeignListModel;
+    use super::super::public_interface::ListModel;
+    use super::super::container_gobject::ContainerGObject;
+    use super::super::list_model_gobject::ListModelGObject;
+    use super::super::vec_list_model::VecListModel;
+    use super::super::filter_list_model::FilterListModel;
+    use super::super::test_helpers::{record_emissions, list_items as items};
+
+    fn new_store(item_type: glib_ffi::GType) -> glib::Object {
+        unsafe { from_glib_full(gio_ffi::g_list_store_new(item_type) as *mut gobject_ffi::GObject) }
+    }
+
+    fn store_ptr(store: &glib::Object) -> *mut gio_ffi::GListStore {
+        let gobj_ptr: *mut gobject_ffi::GObject = store.to_glib_none().0;
+        gobj_ptr as *mut _
+    }
+
+    fn append(store: &glib::Object, item: *mut gobject_ffi::GObject) {
+        // the store takes its own reference
+        unsafe { gio_ffi::g_list_store_append(store_ptr(store), item); }
+    }
+
+    #[test]
+    /* Changes made to a GListStore reach Rust subscribers and adapters. */
+    fn follows_a_list_store() {
+        let store = new_store(ContainerGObject::<u32>::get_type());
+        for value in [1, 2, 3].iter() {
+            append(&store, ContainerGObject::new(*value).0 as *mut _);
+        }
+        let foreign_model = ForeignListModel::<u32>::with_container_items(&store).unwrap();
+        let emissions = record_emissions(&foreign_model);
+        let odd_model = FilterListModel::new(foreign_model.clone(), |item: &u32| item % 2 == 1);
+        assert_eq!(items(&foreign_model), vec![1, 2, 3]);
+        assert_eq!(items(&odd_model), vec![1, 3]);
+
+        append(&store, ContainerGObject::new(5u32).0 as *mut _);
+        unsafe { gio_ffi::g_list_store_remove(store_ptr(&store), 0); }
+        assert_eq!(items(&foreign_model), vec![2, 3, 5]);
+        assert_eq!(items(&odd_model), vec![3, 5]);
+        assert_eq!(*emissions.borrow(), vec![(3, 0, 1), (0, 1, 0)]);
+    }
+
+    #[test]
+    /* Items of an unexpected type are None, objects are downcast. */
+    fn items_are_downcast() {
+        let store = new_store(gobject_ffi::G_TYPE_OBJECT);
+        unsafe {
+            let plain_object = gobject_ffi::g_object_new(gobject_ffi::G_TYPE_OBJECT, ptr::null());
+            append(&store, plain_object);
+            gobject_ffi::g_object_unref(plain_object);
+        }
+        append(&store, ContainerGObject::new(7u32).0 as *mut _);
+
+        let container_model = ForeignListModel::<u32>::with_container_items(&store).unwrap();
+        assert_eq!(container_model.try_get_item(0), None);
+        assert_eq!(container_model.try_get_item(1), Some(7));
+        assert_eq!(container_model.try_get_item(2), None);
+
+        let object_model = ForeignListModel::<glib::Object>::with_object_items(&store).unwrap();
+        assert!(object_model.try_get_item(0).is_some());
+
+        let not_a_list_model: glib::Object = unsafe {
+            from_glib_full(gobject_ffi::g_object_new(gobject_ffi::G_TYPE_OBJECT, ptr::null()))
+        };
+        assert!(ForeignListModel::<u32>::with_container_items(&not_a_list_model).is_none());
+    }
+
+    #[test]
+    /* A ListModelGObject goes back to Rust through C. */
+    fn round_trip_through_list_model_gobject() {
+        let vec_model = VecListModel::from(vec![String::from("a")]);
+        let list_model_gobj = ListModelGObject::new(vec_model.clone());
+        let gobj: glib::Object = unsafe { from_glib_none(list_model_gobj.0 as *mut gobject_ffi::GObject) };
+        let foreign_model = ForeignListModel::<String>::with_container_items(&gobj).unwrap();
+        let emissions = record_emissions(&foreign_model);
+        vec_model.push(String::from("b"));
+        assert_eq!(items(&foreign_model), vec![String::from("a"), String::from("b")]);
+        assert_eq!(*emissions.borrow(), vec![(1, 0, 1)]);
+    }
+}
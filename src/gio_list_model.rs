@@ -0,0 +1,115 @@
+extern crate gobject_sys as gobject_ffi;
+extern crate glib_sys as glib_ffi;
+extern crate gio_sys as gio_ffi;
+extern crate glib;
+
+use std::ptr;
+use std::mem;
+use std::mem::transmute;
+use self::glib::translate::*;
+use self::glib::signal::{connect, SignalHandlerId};
+use super::panic_guard;
+
+/* The GListModel interface as a gtk-rs object,
+   which the gio crate doesn't wrap yet.
+
+   Anything implementing GListModel can be one,
+   eg: a ListModelGObject (see `ListModelGObject::to_gio_list_model()`)
+   or a GListStore. Like other gtk-rs objects, it can be upcast
+   to glib::Object, stored in a glib::Value and downcast back. */
+glib_wrapper! {
+    pub struct GioListModel(Object<gio_ffi::GListModel>);
+
+    match fn {
+        get_type => || gio_ffi::g_list_model_get_type(),
+    }
+}
+
+type ItemsChangedCallback = dyn Fn(&GioListModel, u32, u32, u32) + 'static;
+
+impl GioListModel {
+
+    pub fn get_n_items(&self) -> u32 {
+        unsafe { gio_ffi::g_list_model_get_n_items(self.to_glib_none().0) }
+    }
+
+    /* None if there is no item at `position`. */
+    pub fn get_object(&self, position: u32) -> Option<glib::Object> {
+        unsafe { from_glib_full(gio_ffi::g_list_model_get_item(self.to_glib_none().0, position)) }
+    }
+
+    pub fn items_changed(&self, position: u32, removed: u32, added: u32) {
+        unsafe { gio_ffi::g_list_model_items_changed(self.to_glib_none().0, position, removed, added); }
+    }
+
+    pub fn connect_items_changed<F>(&self, f: F) -> SignalHandlerId
+    where F: Fn(&GioListModel, u32, u32, u32) + 'static {
+        unsafe {
+            let f: Box<Box<ItemsChangedCallback>> = Box::new(Box::new(f));
+            let gobj_ptr: *mut gobject_ffi::GObject = self.to_glib_none().0;
+            let trampoline: unsafe extern "C" fn() = transmute(
+                items_changed_trampoline as unsafe extern "C" fn(_, _, _, _, _)
+            );
+            connect(gobj_ptr, "items-changed", Some(trampoline), Box::into_raw(f) as *mut _)
+        }
+    }
+}
+
+unsafe extern "C" fn items_changed_trampoline(this: *mut gio_ffi::GListModel,
+                                              position: u32, removed: u32, added: u32,
+                                              f: glib_ffi::gpointer) {
+    let f = &*(f as *const Box<ItemsChangedCallback>);
+    let this: GioListModel = from_glib_borrow(this);
+    panic_guard::guard("GioListModel items-changed", || f(&this, position, removed, added));
+}
+
+#[cfg(test)]
+mod test {
+
+    extern crate glib;
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use self::glib::{Cast, ToValue};
+    use super::GioListModel;
+    use super::super::list_model_gobject::ListModelGObject;
+    use super::super::vec_list_model::VecListModel;
+    use super::super::foreign_list_model::ForeignListModel;
+    use super::super::test_helpers::list_items as items;
+
+    #[test]
+    /* A ListModelGObject goes through glib::Object and glib::Value
+       and comes back as the same object. */
+    fn conversions_round_trip() {
+        let vec_model = VecListModel::from(vec![1u8, 2]);
+        let list_model_gobj = ListModelGObject::new(vec_model.clone());
+        let gio_list_model = list_model_gobj.to_gio_list_model();
+        assert_eq!(gio_list_model.get_n_items(), 2);
+        assert!(gio_list_model.get_object(1).is_some());
+        assert!(gio_list_model.get_object(2).is_none());
+
+        let value = gio_list_model.to_value();
+        let object = value.get::<GioListModel>().unwrap().upcast::<glib::Object>();
+        assert_eq!(object, list_model_gobj.to_object());
+
+        let list_model_gobj_again = ListModelGObject::<u8, VecListModel<u8>>::from_object(&object).unwrap();
+        assert_eq!(list_model_gobj_again.0, list_model_gobj.0);
+        assert!(ListModelGObject::<u16, VecListModel<u16>>::from_object(&object).is_none());
+
+        let foreign_model = ForeignListModel::<u8>::with_container_items(&object).unwrap();
+        assert_eq!(items(&foreign_model), vec![1, 2]);
+    }
+
+    #[test]
+    fn items_changed_reaches_gtk_rs_handlers() {
+        let emissions = Rc::new(RefCell::new(Vec::new()));
+        let vec_model = VecListModel::from(vec![1u8]);
+        let gio_list_model = ListModelGObject::new(vec_model.clone()).to_gio_list_model();
+        let emissions_clone = emissions.clone();
+        gio_list_model.connect_items_changed(move |list_model, position, removed, added| {
+            emissions_clone.borrow_mut().push((list_model.get_n_items(), position, removed, added));
+        });
+        vec_model.push(2);
+        assert_eq!(*emissions.borrow(), vec![(2, 1, 0, 1)]);
+    }
+}
@@ -0,0 +1,112 @@
+extern crate gobject_sys as gobject_ffi;
+
+use std::mem;
+use super::GObjectPtrWrapper;
+use super::container_gobject::ContainerGObject;
+
+/* How a ListModelGObject keeps the ContainerGObjects
+   it has handed out, so that asking twice for the same
+   position gives the same GObject. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemCacheMode {
+    /* A new ContainerGObject on every get_item. */
+    Disabled,
+    /* Items are kept alive until an items-changed replaces them,
+       which keeps every item handed out in memory. */
+    Strong,
+    /* Items are reused for as long as someone else keeps them alive.
+       The default. */
+    Weak
+}
+
+enum CacheEntry<T> where T: 'static {
+    Strong(GObjectPtrWrapper<ContainerGObject<T>>),
+    /* GWeakRef must not move once initialised, hence the Box. */
+    Weak(Box<gobject_ffi::GWeakRef>)
+}
+
+impl<T> Drop for CacheEntry<T> where T: 'static {
+    fn drop(&mut self) {
+        if let CacheEntry::Weak(ref mut weak_ref) = *self {
+            unsafe { gobject_ffi::g_weak_ref_clear(&mut **weak_ref); }
+        }
+    }
+}
+
+/* Cached items by position.
+   Positions follow the items-changed emissions of the model,
+   so entries of untouched items survive changes around them. */
+pub struct ItemCache<T> where T: 'static {
+    mode: ItemCacheMode,
+    entries: Vec<Option<CacheEntry<T>>>
+}
+
+impl<T> ItemCache<T> where T: 'static {
+
+    pub fn new(mode: ItemCacheMode) -> Self {
+        ItemCache {
+            mode,
+            entries: Vec::new()
+        }
+    }
+
+    pub fn get_mode(&self) -> ItemCacheMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ItemCacheMode) {
+        self.mode = mode;
+        self.entries.clear();
+    }
+
+    pub fn get(&self, position: u32) -> Option<GObjectPtrWrapper<ContainerGObject<T>>> {
+        match self.entries.get(position as usize) {
+            Some(&Some(CacheEntry::Strong(ref item))) => Some(item.clone()),
+            Some(&Some(CacheEntry::Weak(ref weak_ref))) => unsafe {
+                let weak_ref_ptr = &**weak_ref as *const _ as *mut gobject_ffi::GWeakRef;
+                // returns a new reference, or NULL once the item is gone
+                let gobj_ptr = gobject_ffi::g_weak_ref_get(weak_ref_ptr);
+                if gobj_ptr.is_null() {
+                    None
+                } else {
+                    Some(GObjectPtrWrapper(gobj_ptr as *mut ContainerGObject<T>))
+                }
+            },
+            _ => None
+        }
+    }
+
+    pub fn insert(&mut self, position: u32, item: &GObjectPtrWrapper<ContainerGObject<T>>) {
+        let entry = match self.mode {
+            ItemCacheMode::Disabled => return,
+            ItemCacheMode::Strong => CacheEntry::Strong(item.clone()),
+            ItemCacheMode::Weak => unsafe {
+                let mut weak_ref: Box<gobject_ffi::GWeakRef> = Box::new(mem::zeroed());
+                gobject_ffi::g_weak_ref_init(&mut *weak_ref, item.0 as *mut _);
+                CacheEntry::Weak(weak_ref)
+            }
+        };
+        let position = position as usize;
+        if self.entries.len() <= position {
+            self.entries.resize_with(position + 1, || None);
+        }
+        self.entries[position] = Some(entry);
+    }
+
+    /* Forget the item at `position`, which changed in place. */
+    pub fn forget(&mut self, position: u32) {
+        if let Some(entry) = self.entries.get_mut(position as usize) {
+            *entry = None;
+        }
+    }
+
+    /* Forget the removed items and shift the ones after them. */
+    pub fn items_changed(&mut self, position: u32, removed: u32, added: u32) {
+        let position = position as usize;
+        if position >= self.entries.len() {
+            return;
+        }
+        let removed_end = (position + removed as usize).min(self.entries.len());
+        self.entries.splice(position..removed_end, (0..added).map(|_| None));
+    }
+}
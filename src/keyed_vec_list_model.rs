@@ -0,0 +1,346 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem;
+use super::public_interface::{ListModel, KeyedListModel};
+use super::change_notifier::ChangeNotifier;
+
+/* A VecListModel whose items have unique keys, eg: "the row for job #42".
+
+   A key to index map makes index_of() and the by-key lookups O(1).
+   Inserting or removing only marks the stored indices after the position as stale,
+   the first lookup of one of them afterwards computes the keys from there on again.
+   So a burst of changes costs one O(n) refresh instead of one per change.
+   Adding an item whose key is already there panics, leaving the model as it was.
+
+   Like VecListModel, clones refer to the same list. */
+pub struct KeyedVecListModel<K, T>
+where K: Eq + Hash {
+    inner: Rc<KeyedInner<K, T>>
+}
+
+struct KeyedInner<K, T>
+where K: Eq + Hash {
+    items: RefCell<KeyedItems<K, T>>,
+    key_of: Box<dyn Fn(&T) -> K>,
+    change_notifier: ChangeNotifier
+}
+
+struct KeyedItems<K, T>
+where K: Eq + Hash {
+    items: Vec<T>,
+    // every key, with the index of its item if below `stale_from`
+    indices: HashMap<K, u32>,
+    // inserts and removals before them may have moved the items from here on
+    stale_from: u32
+}
+
+impl<K, T> KeyedItems<K, T>
+where K: Eq + Hash {
+
+    fn assert_new_key(&self, key: &K) {
+        if self.indices.contains_key(key) {
+            panic!("Duplicate key: KeyedVecListModel");
+        }
+    }
+
+    fn add_key(&mut self, key: K, index: u32) {
+        self.assert_new_key(&key);
+        self.indices.insert(key, index);
+    }
+
+    /* After an item was inserted or removed at `position`. */
+    fn mark_stale(&mut self, position: u32) {
+        self.stale_from = self.stale_from.min(position);
+    }
+
+    fn index_of(&mut self, key: &K, key_of: &dyn Fn(&T) -> K) -> Option<u32> {
+        let index = *self.indices.get(key)?;
+        if index < self.stale_from {
+            return Some(index);
+        }
+        for (index, item) in self.items.iter().enumerate().skip(self.stale_from as usize) {
+            self.indices.insert(key_of(item), index as u32);
+        }
+        self.stale_from = self.items.len() as u32;
+        self.indices.get(key).cloned()
+    }
+}
+
+impl<K, T> KeyedVecListModel<K, T>
+where K: Eq + Hash {
+
+    pub fn new<KF>(key_of: KF) -> Self
+    where KF: Fn(&T) -> K + 'static {
+        Self::with_items(Vec::new(), key_of)
+    }
+
+    pub fn with_items<KF>(items: Vec<T>, key_of: KF) -> Self
+    where KF: Fn(&T) -> K + 'static {
+        let mut keyed_items = KeyedItems { items: Vec::new(), indices: HashMap::new(), stale_from: 0 };
+        for (index, item) in items.iter().enumerate() {
+            keyed_items.add_key(key_of(item), index as u32);
+        }
+        keyed_items.stale_from = items.len() as u32;
+        keyed_items.items = items;
+        KeyedVecListModel {
+            inner: Rc::new(KeyedInner {
+                items: RefCell::new(keyed_items),
+                key_of: Box::new(key_of),
+                change_notifier: ChangeNotifier::new()
+            })
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.inner.items.borrow().items.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.items.borrow().items.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.inner.items.borrow().indices.contains_key(key)
+    }
+
+    pub fn push(&self, item: T) {
+        let position = {
+            let mut items = self.inner.items.borrow_mut();
+            let position = items.items.len() as u32;
+            items.add_key((self.inner.key_of)(&item), position);
+            items.items.push(item);
+            if items.stale_from == position {
+                items.stale_from += 1;
+            }
+            position
+        };
+        self.inner.change_notifier.emit(position, 0, 1);
+    }
+
+    pub fn insert(&self, index: u32, item: T) {
+        {
+            let mut items = self.inner.items.borrow_mut();
+            assert!(index as usize <= items.items.len(), "Index out of bounds: KeyedVecListModel");
+            let key = (self.inner.key_of)(&item);
+            items.assert_new_key(&key);
+            items.indices.insert(key, index);
+            items.items.insert(index as usize, item);
+            items.mark_stale(index);
+        }
+        self.inner.change_notifier.emit(index, 0, 1);
+    }
+
+    pub fn remove(&self, index: u32) -> T {
+        let item = {
+            let mut items = self.inner.items.borrow_mut();
+            let item = items.items.remove(index as usize);
+            items.indices.remove(&(self.inner.key_of)(&item));
+            items.mark_stale(index);
+            item
+        };
+        self.inner.change_notifier.emit(index, 1, 0);
+        item
+    }
+
+    pub fn remove_by_key(&self, key: &K) -> Option<T> {
+        let index = self.index_of(key)?;
+        Some(self.remove(index))
+    }
+
+    /* Changes the item with `key` and reports its row as replaced.
+       `update` gets a copy of the item and may read the model,
+       the model keeps the old item until `update` returns.
+       `update` may change the key, as long as it stays unique.
+       false if there is no item with `key`. */
+    pub fn update_by_key<F>(&self, key: &K, update: F) -> bool
+    where T: Clone, F: FnOnce(&mut T) {
+        let mut item = match self.get_by_key(key) {
+            Some(item) => item,
+            None => return false
+        };
+        update(&mut item);
+        let new_key = (self.inner.key_of)(&item);
+        let index = {
+            let mut items = self.inner.items.borrow_mut();
+            // `update` may have removed or moved the item
+            let index = match items.index_of(key, &*self.inner.key_of) {
+                Some(index) => index,
+                None => return false
+            };
+            if new_key != *key {
+                items.add_key(new_key, index);
+                items.indices.remove(key);
+            }
+            items.items[index as usize] = item;
+            index
+        };
+        self.inner.change_notifier.emit(index, 1, 1);
+        true
+    }
+
+    pub fn get_by_key(&self, key: &K) -> Option<T>
+    where T: Clone {
+        let mut items = self.inner.items.borrow_mut();
+        let index = items.index_of(key, &*self.inner.key_of)?;
+        Some(items.items[index as usize].clone())
+    }
+
+    pub fn index_of(&self, key: &K) -> Option<u32> {
+        self.inner.items.borrow_mut().index_of(key, &*self.inner.key_of)
+    }
+
+    pub fn clear(&self) {
+        let removed = {
+            let mut items = self.inner.items.borrow_mut();
+            items.indices.clear();
+            items.stale_from = 0;
+            mem::take(&mut items.items)
+        };
+        self.inner.change_notifier.emit(0, removed.len() as u32, 0);
+    }
+}
+
+impl<K, T> Clone for KeyedVecListModel<K, T>
+where K: Eq + Hash {
+    fn clone(&self) -> Self {
+        KeyedVecListModel { inner: self.inner.clone() }
+    }
+}
+
+impl<K, T> ListModel<T> for KeyedVecListModel<K, T>
+where K: Eq + Hash, T: Clone {
+    fn get_n_items(&self) -> u32 {
+        self.len()
+    }
+
+    fn get_item(&self, index: u32) -> T {
+        self.inner.items.borrow().items[index as usize].clone()
+    }
+
+    fn try_get_item(&self, index: u32) -> Option<T> {
+        self.inner.items.borrow().items.get(index as usize).cloned()
+    }
+
+    fn get_change_notifier(&self) -> Option<&ChangeNotifier> {
+        Some(&self.inner.change_notifier)
+    }
+}
+
+impl<K, T> KeyedListModel<K, T> for KeyedVecListModel<K, T>
+where K: Eq + Hash, T: Clone {
+    fn key_of(&self, item: &T) -> K {
+        (self.inner.key_of)(item)
+    }
+
+    fn index_of(&self, key: &K) -> Option<u32> {
+        KeyedVecListModel::index_of(self, key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::rc::Rc;
+    use std::cell::Cell;
+    use super::KeyedVecListModel;
+    use super::super::public_interface::KeyedListModel;
+    use super::super::vec_list_model::VecListModel;
+    use super::super::test_helpers::{record_emissions, list_items as items};
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Job {
+        id: u32,
+        progress: u32
+    }
+
+    fn job(id: u32, progress: u32) -> Job {
+        Job { id, progress }
+    }
+
+    #[test]
+    /* Indices follow inserts and removals. */
+    fn keys_are_found_after_changes() {
+        let model = KeyedVecListModel::with_items(vec![job(10, 0), job(20, 0), job(30, 0)], |job: &Job| job.id);
+        let emissions = record_emissions(&model);
+        model.insert(0, job(5, 0));
+        assert_eq!(model.remove_by_key(&20), Some(job(20, 0)));
+        model.push(job(40, 0));
+        assert_eq!(model.remove_by_key(&20), None);
+        assert_eq!((model.index_of(&5), model.index_of(&10), model.index_of(&30), model.index_of(&40)),
+                   (Some(0), Some(1), Some(2), Some(3)));
+        assert_eq!(*emissions.borrow(), vec![(0, 0, 1), (2, 1, 0), (3, 0, 1)]);
+    }
+
+    #[test]
+    /* Changes only mark the indices after them as stale,
+       the keys are computed again once, on the next lookup past the change. */
+    fn stale_indices_are_refreshed_once() {
+        let key_calls = Rc::new(Cell::new(0));
+        let key_calls_clone = key_calls.clone();
+        let model = KeyedVecListModel::with_items(vec![job(1, 0), job(2, 0), job(3, 0), job(4, 0)], move |job: &Job| {
+            key_calls_clone.set(key_calls_clone.get() + 1);
+            job.id
+        });
+        model.remove(1);
+        model.insert(2, job(5, 0));
+        key_calls.set(0);
+        assert_eq!(model.index_of(&1), Some(0));
+        assert_eq!(key_calls.get(), 0);
+        assert_eq!(model.index_of(&4), Some(3));
+        assert_eq!((model.index_of(&3), model.index_of(&5)), (Some(1), Some(2)));
+        assert_eq!(key_calls.get(), 3);
+    }
+
+    #[test]
+    /* An update replaces the one row of the item, even when it changes the key. */
+    fn update_by_key_reports_one_row() {
+        let model = KeyedVecListModel::with_items(vec![job(1, 0), job(2, 0)], |job: &Job| job.id);
+        let emissions = record_emissions(&model);
+        assert!(model.update_by_key(&2, |job| job.progress = 50));
+        assert!(!model.update_by_key(&3, |job| job.progress = 50));
+        assert!(model.update_by_key(&1, |job| job.id = 7));
+        assert_eq!(items(&model), vec![job(7, 0), job(2, 50)]);
+        assert_eq!(model.get_by_key(&7), Some(job(7, 0)));
+        assert!(!model.contains_key(&1));
+        assert_eq!(*emissions.borrow(), vec![(1, 1, 1), (0, 1, 1)]);
+    }
+
+    #[test]
+    /* `update` can read the model, and a duplicate key leaves the model as it was. */
+    fn update_by_key_runs_without_a_borrow() {
+        let model = KeyedVecListModel::with_items(vec![job(1, 0), job(2, 0)], |job: &Job| job.id);
+        let model_clone = model.clone();
+        assert!(model.update_by_key(&1, |job| job.progress = model_clone.len()));
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            model.update_by_key(&1, |job| job.id = 2);
+        }));
+        assert!(result.is_err());
+        assert_eq!(items(&model), vec![job(1, 2), job(2, 0)]);
+        assert_eq!((model.index_of(&1), model.index_of(&2)), (Some(0), Some(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate key")]
+    fn duplicate_keys_panic() {
+        let model = KeyedVecListModel::with_items(vec![job(1, 0)], |job: &Job| job.id);
+        model.push(job(1, 5));
+    }
+
+    #[test]
+    /* Models without a key map are searched item by item. */
+    fn default_index_of_scans() {
+        struct JobList(VecListModel<Job>);
+        impl super::ListModel<Job> for JobList {
+            fn get_n_items(&self) -> u32 { self.0.get_n_items() }
+            fn get_item(&self, index: u32) -> Job { self.0.get_item(index) }
+        }
+        impl KeyedListModel<u32, Job> for JobList {
+            fn key_of(&self, job: &Job) -> u32 { job.id }
+        }
+        let job_list = JobList(VecListModel::from(vec![job(3, 0), job(4, 0)]));
+        assert_eq!(job_list.index_of(&4), Some(1));
+        assert_eq!(job_list.index_of(&5), None);
+    }
+}
@@ -1,9 +1,40 @@
+#[macro_use]
+extern crate glib;
+
 mod public_interface;
 mod gobject_ptr_wrapper;
 mod container_gobject;
 mod list_model_gobject;
+mod gio_list_model;
+mod change_notifier;
+mod pending_changes;
+mod item_cache;
+mod panic_guard;
+mod diff;
+mod vec_list_model;
+mod keyed_vec_list_model;
+mod filter_list_model;
+mod sort_list_model;
+mod map_list_model;
+mod concat_list_model;
+mod foreign_list_model;
+mod list_selection;
+mod model_widget;
 mod test_helpers;
 
 
 pub use public_interface::*;
-pub use gobject_ptr_wrapper::GObjectPtrWrapper;
\ No newline at end of file
+pub use gobject_ptr_wrapper::GObjectPtrWrapper;
+pub use list_model_gobject::ListModelGObject;
+pub use gio_list_model::GioListModel;
+pub use change_notifier::{ChangeNotifier, HandlerId};
+pub use vec_list_model::VecListModel;
+pub use keyed_vec_list_model::KeyedVecListModel;
+pub use filter_list_model::FilterListModel;
+pub use sort_list_model::SortListModel;
+pub use map_list_model::MapListModel;
+pub use concat_list_model::ConcatListModel;
+pub use foreign_list_model::ForeignListModel;
+pub use list_selection::ListSelection;
+pub use item_cache::ItemCacheMode;
+pub use panic_guard::set_panic_handler;
\ No newline at end of file
@@ -2,6 +2,7 @@ extern crate libc;
 extern crate gobject_sys as gobject_ffi;
 extern crate glib_sys as glib_ffi;
 extern crate gio_sys as gio_ffi;
+extern crate glib;
 
 use std::ptr;
 use std::mem;
@@ -9,10 +10,18 @@ use std::collections::HashMap;
 use std::any::TypeId;
 use std::ffi::CString;
 use std::marker::PhantomData;
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
 use self::libc::c_void;
+use self::glib::translate::*;
+use self::glib::object::IsA;
 use super::public_interface::ListModel;
 use super::GObjectPtrWrapper;
 use super::container_gobject::ContainerGObject;
+use super::item_cache::{ItemCache, ItemCacheMode};
+use super::panic_guard;
+use super::change_notifier::HandlerId;
+use super::gio_list_model::GioListModel;
 
 /* Wraps any type implementing public_interface::ListModel
    to be passed into the gtk ffi functions which expect GObjects. */
@@ -20,15 +29,28 @@ use super::container_gobject::ContainerGObject;
 pub struct ListModelGObject<T, LM> where T:'static, LM: ListModel<T> + 'static {
     parent: gobject_ffi::GObject,
     list_model: LM,
+    // connection to the ChangeNotifier of list_model, if it has one
+    change_handler_id: Option<HandlerId>,
+    item_cache: RefCell<ItemCache<T>>,
+    // true while `items-changed` handlers run
+    emitting: Cell<bool>,
+    item_updated_callbacks: RefCell<Vec<(usize, ItemUpdatedCallback<T, LM>)>>,
+    next_callback_id: Cell<usize>,
     phantom: PhantomData<T>
 }
 
+type ItemUpdatedCallback<T, LM> = Rc<dyn Fn(&ListModelGObject<T, LM>, u32) -> bool>;
+
 #[repr(C)]
 struct ListModelGObjectClass(gobject_ffi::GObjectClass);
 
 impl<T, LM> ListModelGObject<T, LM> where T:'static, LM: ListModel<T> +'static {
 
     pub fn new(list_model: LM) -> GObjectPtrWrapper<Self> {
+        Self::with_item_cache(list_model, ItemCacheMode::Weak)
+    }
+
+    pub fn with_item_cache(list_model: LM, item_cache_mode: ItemCacheMode) -> GObjectPtrWrapper<Self> {
         unsafe {
             let self_gobj_ptr = gobject_ffi::g_object_new(
                 Self::get_type(),
@@ -36,10 +58,138 @@ impl<T, LM> ListModelGObject<T, LM> where T:'static, LM: ListModel<T> +'static {
             ) as *mut Self;
 
             ptr::write(&mut (*self_gobj_ptr).list_model, list_model);
+            ptr::write(&mut (*self_gobj_ptr).item_cache, RefCell::new(ItemCache::new(item_cache_mode)));
+            ptr::write(&mut (*self_gobj_ptr).emitting, Cell::new(false));
+            ptr::write(&mut (*self_gobj_ptr).item_updated_callbacks, RefCell::new(Vec::new()));
+            ptr::write(&mut (*self_gobj_ptr).next_callback_id, Cell::new(0));
+
+            /* The handler is disconnected in dispose(),
+               so self_gobj_ptr is valid whenever it is called. */
+            let change_handler_id = (*self_gobj_ptr).list_model.get_change_notifier().map(|notifier| {
+                notifier.connect_with_item_updated(
+                    move |position, removed, added| (*self_gobj_ptr).items_changed(position, removed, added),
+                    move |position| (*self_gobj_ptr).item_updated(position)
+                )
+            });
+            ptr::write(&mut (*self_gobj_ptr).change_handler_id, change_handler_id);
             GObjectPtrWrapper::<Self>(self_gobj_ptr)
         }
     }
 
+    pub fn get(&self) -> &LM {
+        &self.list_model
+    }
+
+    pub fn get_item_cache_mode(&self) -> ItemCacheMode {
+        self.item_cache.borrow().get_mode()
+    }
+
+    /* Drops the items cached so far. */
+    pub fn set_item_cache_mode(&self, item_cache_mode: ItemCacheMode) {
+        self.item_cache.borrow_mut().set_mode(item_cache_mode);
+    }
+
+    /* A new reference to this object as a gtk-rs GioListModel,
+       to pass to gtk-rs APIs, connect signals or store in a glib::Value. */
+    pub fn to_gio_list_model(&self) -> GioListModel {
+        unsafe { from_glib_none(self as *const Self as *mut gio_ffi::GListModel) }
+    }
+
+    pub fn to_object(&self) -> glib::Object {
+        unsafe { from_glib_none(self as *const Self as *mut gobject_ffi::GObject) }
+    }
+
+    /* None unless `object` is a ListModelGObject of this very LM. */
+    pub fn from_object<O>(object: &O) -> Option<GObjectPtrWrapper<Self>>
+    where O: IsA<glib::Object> {
+        unsafe {
+            let gobj_ptr: *mut gobject_ffi::GObject = object.to_glib_none().0;
+            let is_self = gobject_ffi::g_type_check_instance_is_a(
+                gobj_ptr as *mut gobject_ffi::GTypeInstance,
+                Self::get_type()
+            );
+            if is_self == glib_ffi::GFALSE {
+                return None;
+            }
+            gobject_ffi::g_object_ref(gobj_ptr as *mut _);
+            Some(GObjectPtrWrapper::<Self>(gobj_ptr as *mut Self))
+        }
+    }
+
+    /* The ContainerGObject for `position`,
+       which is the same object for as long as the item cache keeps it.
+       None if the model has no item there. */
+    pub fn get_item(&self, position: u32) -> Option<GObjectPtrWrapper<ContainerGObject<T>>> {
+        if let Some(item) = self.item_cache.borrow().get(position) {
+            return Some(item);
+        }
+        let item = ContainerGObject::new(self.list_model.try_get_item(position)?);
+        self.item_cache.borrow_mut().insert(position, &item);
+        Some(item)
+    }
+
+    /* Emits the `items-changed` signal of GListModel,
+       so that whoever is bound to this object (eg: GtkListBox)
+       can update the rows in [position, position+removed)
+       with `added` new ones.
+       Cached items of the replaced rows are dropped first. */
+    pub fn items_changed(&self, position: u32, removed: u32, added: u32) {
+        self.item_cache.borrow_mut().items_changed(position, removed, added);
+        let was_emitting = self.emitting.replace(true);
+        unsafe {
+            gio_ffi::g_list_model_items_changed(
+                self as *const Self as *mut gio_ffi::GListModel,
+                position,
+                removed,
+                added
+            );
+        }
+        self.emitting.set(was_emitting);
+    }
+
+    /* The item at `position` changed in place.
+       Its cached item is dropped, so the next get_item() asks the model again,
+       and the item-updated callbacks follow the change, eg: by updating rows in place.
+       If there are none, or one of them can't, `items-changed` (position, 1, 1)
+       is emitted instead. Positions past the end are ignored. */
+    pub fn item_updated(&self, position: u32) {
+        if position >= self.list_model.get_n_items() {
+            return;
+        }
+        self.item_cache.borrow_mut().forget(position);
+        let callbacks: Vec<_> = self.item_updated_callbacks.borrow().iter()
+            .map(|(_, callback)| callback.clone())
+            .collect();
+        let updated = !callbacks.is_empty() && callbacks.iter().all(|callback| {
+            panic_guard::guard("item-updated", || callback(self, position)).unwrap_or(false)
+        });
+        if !updated {
+            self.items_changed(position, 1, 1);
+        }
+    }
+
+    /* `callback(self, position)` when the item at `position` changed in place,
+       returning false if it can't follow the change without an `items-changed`.
+       Keep the returned id to disconnect it. */
+    pub fn connect_item_updated<F>(&self, callback: F) -> usize
+    where F: Fn(&Self, u32) -> bool + 'static {
+        let id = self.next_callback_id.get();
+        self.next_callback_id.set(id + 1);
+        self.item_updated_callbacks.borrow_mut().push((id, Rc::new(callback)));
+        id
+    }
+
+    pub fn disconnect_item_updated(&self, callback_id: usize) {
+        self.item_updated_callbacks.borrow_mut().retain(|&(id, _)| id != callback_id);
+    }
+
+    /* Whether an `items-changed` emission is in progress,
+       eg: to tell the selection changes gtk makes
+       while recreating rows from the ones made by the user. */
+    pub fn is_emitting(&self) -> bool {
+        self.emitting.get()
+    }
+
     extern "C" fn class_init(klass_ptr: *mut c_void, _data: *mut c_void) {
         let klass_ptr = klass_ptr as *mut gobject_ffi::GObjectClass;
         unsafe { (*klass_ptr).dispose = Some(Self::dispose); }
@@ -48,7 +198,16 @@ impl<T, LM> ListModelGObject<T, LM> where T:'static, LM: ListModel<T> +'static {
     extern "C" fn dispose(gobj_ptr: *mut gobject_ffi::GObject) {
         unsafe {
             let self_ptr = gobj_ptr as *mut Self;
-            ptr::drop_in_place(&mut (*self_ptr).list_model);
+            panic_guard::guard("ListModelGObject::dispose", || {
+                if let Some(handler_id) = (*self_ptr).change_handler_id.take() {
+                    if let Some(notifier) = (*self_ptr).list_model.get_change_notifier() {
+                        notifier.disconnect(handler_id);
+                    }
+                }
+                ptr::drop_in_place(&mut (*self_ptr).item_updated_callbacks);
+                ptr::drop_in_place(&mut (*self_ptr).item_cache);
+                ptr::drop_in_place(&mut (*self_ptr).list_model);
+            });
             let parent_class: *mut gobject_ffi::GObjectClass = gobject_ffi::g_type_class_peek_parent(
                 gobject_ffi::g_type_class_peek_static(Self::get_type())
             ) as *mut _;
@@ -136,17 +295,17 @@ impl<T, LM> ListModelGObject<T, LM> where T:'static, LM: ListModel<T> +'static {
 
     extern "C" fn g_list_model_get_n_items(list_model_ptr: *mut gio_ffi::GListModel) -> u32 {
         let self_ptr = list_model_ptr as *mut Self;
-        unsafe {
+        panic_guard::guard("ListModel::get_n_items", || unsafe {
             (*self_ptr).list_model.get_n_items()
-        }
+        }).unwrap_or(0)
     }
 
     extern "C" fn g_list_model_get_item(list_model_ptr: *mut gio_ffi::GListModel, position: u32) -> glib_ffi::gpointer {
         let self_ptr = list_model_ptr as *mut Self;
-        unsafe {
-            let g_obj_ptr_wrapper = ContainerGObject::new((*self_ptr).list_model.get_item(position));
-            g_obj_ptr_wrapper.to_glib_full() as *mut _
-        }
+        panic_guard::guard("ListModel::get_item", || unsafe {
+            (*self_ptr).get_item(position)
+        }).and_then(|item| item)
+          .map_or(ptr::null_mut(), |item| item.to_glib_full() as glib_ffi::gpointer)
     }
 
 }
@@ -227,6 +386,192 @@ mod test_object_creation {
     }
 }
 
+#[cfg(test)]
+mod test_items_changed {
+
+    extern crate gobject_sys as gobject_ffi;
+    extern crate glib_sys as glib_ffi;
+    extern crate gio_sys as gio_ffi;
+
+    use std::mem;
+    use super::ListModelGObject;
+
+    struct U8LM();
+    impl super::ListModel<u8> for U8LM {
+        fn get_n_items(&self) -> u32 {0}
+        fn get_item(&self, _index: u32) -> u8 {unimplemented!()}
+    }
+
+    struct ThreeLM();
+    impl super::ListModel<u8> for ThreeLM {
+        fn get_n_items(&self) -> u32 {3}
+        fn get_item(&self, index: u32) -> u8 {index as u8}
+    }
+
+    extern "C" fn record_items_changed(_list_model_ptr: *mut gio_ffi::GListModel,
+                                       position: u32, removed: u32, added: u32,
+                                       user_data: glib_ffi::gpointer) {
+        let emissions = user_data as *mut Vec<(u32, u32, u32)>;
+        unsafe { (*emissions).push((position, removed, added)); }
+    }
+
+    /* Pushes the `items-changed` emissions of `list_model_gobj` into `emissions`,
+       which must outlive it. */
+    fn record_emissions<T, LM>(list_model_gobj: &ListModelGObject<T, LM>, emissions: &mut Vec<(u32, u32, u32)>)
+    where LM: super::ListModel<T> {
+        unsafe {
+            let callback: unsafe extern "C" fn() = mem::transmute(
+                record_items_changed as extern "C" fn(_, _, _, _, _)
+            );
+            gobject_ffi::g_signal_connect_data(
+                list_model_gobj as *const _ as *mut _,
+                b"items-changed\0".as_ptr() as *const _,
+                Some(callback),
+                emissions as *mut _ as glib_ffi::gpointer,
+                None,
+                gobject_ffi::GConnectFlags::empty()
+            );
+        }
+    }
+
+    #[test]
+    /* items_changed() should reach handlers connected
+       to the `items-changed` signal from the C side. */
+    fn items_changed_is_emitted() {
+        let mut emissions: Vec<(u32, u32, u32)> = Vec::new();
+        let list_model_gobj = ListModelGObject::new(U8LM());
+        record_emissions(&list_model_gobj, &mut emissions);
+        list_model_gobj.items_changed(2, 1, 3);
+        list_model_gobj.items_changed(0, 0, 1);
+        assert_eq!(emissions, vec![(2, 1, 3), (0, 0, 1)]);
+    }
+
+    #[test]
+    /* Without item-updated callbacks, eg: when nothing is bound,
+       an item update is emitted as a replacement of the item.
+       It isn't when every callback followed it. */
+    fn unbound_item_updates_are_emitted() {
+        let mut emissions: Vec<(u32, u32, u32)> = Vec::new();
+        let list_model_gobj = ListModelGObject::new(ThreeLM());
+        record_emissions(&list_model_gobj, &mut emissions);
+        list_model_gobj.item_updated(1);
+        list_model_gobj.item_updated(3);
+        assert_eq!(emissions, vec![(1, 1, 1)]);
+
+        let callback_id = list_model_gobj.connect_item_updated(|_, _| true);
+        list_model_gobj.item_updated(2);
+        assert_eq!(emissions, vec![(1, 1, 1)]);
+
+        list_model_gobj.connect_item_updated(|_, _| false);
+        list_model_gobj.item_updated(2);
+        list_model_gobj.disconnect_item_updated(callback_id);
+        list_model_gobj.item_updated(0);
+        assert_eq!(emissions, vec![(1, 1, 1), (2, 1, 1), (0, 1, 1)]);
+    }
+}
+
+#[cfg(test)]
+mod test_item_cache {
+
+    extern crate gio_sys as gio_ffi;
+    extern crate gobject_sys as gobject_ffi;
+
+    use super::ListModelGObject;
+    use super::super::item_cache::ItemCacheMode;
+
+    struct IndexLM();
+    impl super::ListModel<u32> for IndexLM {
+        fn get_n_items(&self) -> u32 {3}
+        fn get_item(&self, index: u32) -> u32 {index}
+    }
+
+    /* get_item through GListModel, the way gtk calls it.
+       The returned reference is released right away,
+       only the address is kept for comparison. */
+    fn get_item_ptr(list_model_gobj: &ListModelGObject<u32, IndexLM>, position: u32) -> usize {
+        unsafe {
+            let item_ptr = gio_ffi::g_list_model_get_item(
+                list_model_gobj as *const _ as *mut gio_ffi::GListModel,
+                position
+            );
+            gobject_ffi::g_object_unref(item_ptr as *mut _);
+            item_ptr as usize
+        }
+    }
+
+    #[test]
+    /* The same position gives the same GObject,
+       until an items-changed replaces it. */
+    fn strong_cache_keeps_identity() {
+        let list_model_gobj = ListModelGObject::with_item_cache(IndexLM(), ItemCacheMode::Strong);
+        let first = get_item_ptr(&list_model_gobj, 0);
+        let second = get_item_ptr(&list_model_gobj, 1);
+        assert_eq!(get_item_ptr(&list_model_gobj, 0), first);
+        assert_eq!(get_item_ptr(&list_model_gobj, 1), second);
+
+        // inserting in front shifts the cached items
+        list_model_gobj.items_changed(0, 0, 1);
+        assert_eq!(get_item_ptr(&list_model_gobj, 1), first);
+        assert_eq!(get_item_ptr(&list_model_gobj, 2), second);
+
+        // kept alive, so the address can't be reused by the replacement
+        let first_item = list_model_gobj.get_item(1).unwrap();
+        list_model_gobj.items_changed(1, 1, 1);
+        assert_ne!(list_model_gobj.get_item(1).unwrap().0, first_item.0);
+    }
+
+    #[test]
+    /* Weakly cached items are reused while somebody holds them. */
+    fn weak_cache_reuses_live_items() {
+        let list_model_gobj = ListModelGObject::new(IndexLM());
+        let held_item = list_model_gobj.get_item(2).unwrap();
+        assert_eq!(list_model_gobj.get_item(2).unwrap().0, held_item.0);
+        assert_eq!(*list_model_gobj.get_item(2).unwrap().get(), 2);
+    }
+
+    #[test]
+    /* Positions past the end have no item, and nothing gets cached for them. */
+    fn missing_items_are_none() {
+        let list_model_gobj = ListModelGObject::new(IndexLM());
+        assert!(list_model_gobj.get_item(3).is_none());
+        assert_eq!(get_item_ptr(&list_model_gobj, 3), 0);
+    }
+
+    #[test]
+    fn disabled_cache_creates_new_items() {
+        let list_model_gobj = ListModelGObject::with_item_cache(IndexLM(), ItemCacheMode::Disabled);
+        let held_item = list_model_gobj.get_item(0).unwrap();
+        assert_ne!(list_model_gobj.get_item(0).unwrap().0, held_item.0);
+    }
+}
+
+#[cfg(test)]
+mod test_panic_safety {
+
+    extern crate gio_sys as gio_ffi;
+
+    use super::ListModelGObject;
+
+    struct PanickingLM();
+    impl super::ListModel<u8> for PanickingLM {
+        fn get_n_items(&self) -> u32 {panic!("get_n_items panicked")}
+        fn get_item(&self, index: u32) -> u8 {panic!("Index out of bounds: {}", index)}
+    }
+
+    #[test]
+    /* Panics in the model don't unwind into C,
+       gtk gets 0 items and NULL instead. */
+    fn panics_are_contained() {
+        super::panic_guard::set_panic_handler(|_, _| ());
+        let list_model_gobj = ListModelGObject::new(PanickingLM());
+        let list_model_ptr = list_model_gobj.0 as *mut gio_ffi::GListModel;
+        unsafe {
+            assert_eq!(gio_ffi::g_list_model_get_n_items(list_model_ptr), 0);
+            assert!(gio_ffi::g_list_model_get_item(list_model_ptr, 3).is_null());
+        }
+    }
+}
+
 #[cfg(test)]
 mod test_list_box_functionality {
 
@@ -238,7 +583,7 @@ mod test_list_box_functionality {
     extern crate gio_sys as gio_ffi;
 
     use std::ptr;
-    use super::super::test_helpers::RefCountTestDouble;
+    use super::super::test_helpers::{RefCountTestDouble, gtk_test};
     use super::super::container_gobject::ContainerGObject;
     use super::ListModelGObject;
     use self::gtk::prelude::*;
@@ -299,34 +644,32 @@ mod test_list_box_functionality {
     }
 
     /* I'm not sure if this test really belongs here among the unit tests.
-       Need to have a look at the new GUI testing library of gtk-rs. */
+       Need to have a look at the new GUI testing library of gtk-rs.
+       It doesn't show a window: gtk::main() would block the other gtk tests. */
     #[test]
     fn try_creating_a_list_box() {
 
-        gtk::init().unwrap();
-        let list_box = gtk::ListBox::new();
-        let mut ref_count = 0 as isize;
-        let list_model_gobj = ListModelGObject::new(ListModelTestImpl {
-            item: StringListItem {
-                obj_count: RefCountTestDouble {ref_count: &mut ref_count as *mut _},
-                value: String::from("Hello World!")
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            let mut ref_count = 0 as isize;
+            let list_model_gobj = ListModelGObject::new(ListModelTestImpl {
+                item: StringListItem {
+                    obj_count: RefCountTestDouble {ref_count: &mut ref_count as *mut _},
+                    value: String::from("Hello World!")
+                }
+            });
+            unsafe {
+                gtk_ffi::gtk_list_box_bind_model(list_box.to_glib_none().0,
+                                                 list_model_gobj.to_glib_full() as *mut _,
+                                                 Some(create_widget),
+                                                 ptr::null_mut(),
+                                                 None);
             }
+            assert_eq!(list_box.get_children().len(), 1);
+            let label = list_box.get_row_at_index(0)
+                .and_then(|row| row.get_child())
+                .and_then(|child| child.downcast::<gtk::Label>().ok());
+            assert_eq!(label.and_then(|label| label.get_text()), Some(String::from("Hello World!")));
         });
-        unsafe {
-            gtk_ffi::gtk_list_box_bind_model(list_box.to_glib_none().0,
-                                             list_model_gobj.to_glib_full() as *mut _,
-                                             Some(create_widget),
-                                             ptr::null_mut(),
-                                             None);
-        }
-        let window = gtk::Window::new(gtk::WindowType::Toplevel);
-        window.add(&list_box);
-        window.connect_delete_event(|_, _| {
-            gtk::main_quit();
-            Inhibit(false)
-        });
-        window.show_all();
-        gtk::main();
-
     }
 }
\ No newline at end of file
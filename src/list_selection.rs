@@ -0,0 +1,402 @@
+extern crate gtk;
+extern crate glib;
+
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use self::gtk::prelude::*;
+use self::glib::ObjectExt;
+use self::glib::signal::SignalHandlerId;
+use super::public_interface::{ListModel, BoundListModel};
+use super::list_model_gobject::ListModelGObject;
+use super::gio_list_model::GioListModel;
+use super::GObjectPtrWrapper;
+use super::panic_guard;
+
+/* Keeps track of which items of a bound model are selected,
+   across the rows GtkListBox recreates when the model changes.
+
+   The selection follows the rows the user selects, and
+   on `items-changed` the indices after the change are shifted.
+   Selected items inside the replaced range are dropped,
+   unless made `with_key` and an added item has the same key,
+   eg: an item replaced by its updated version stays selected.
+
+   The selection mode is the one of the list box.
+   In gtk::SelectionMode::Browse, removing the selected item
+   selects the one taking its place. Clones share the same state. */
+pub struct ListSelection<T, LM>
+where T: 'static, LM: ListModel<T> + 'static {
+    inner: Rc<SelectionInner<T, LM>>
+}
+
+type SameItem<T> = Box<dyn Fn(&T, &T) -> bool>;
+type SelectionCallback<T> = Rc<dyn Fn(&[(u32, T)])>;
+
+struct SelectionInner<T, LM>
+where T: 'static, LM: ListModel<T> + 'static {
+    list_box: gtk::ListBox,
+    list_model_gobj: GObjectPtrWrapper<ListModelGObject<T, LM>>,
+    gio_list_model: GioListModel,
+    // sorted by index
+    selected: RefCell<Vec<Selected<T>>>,
+    // whether two items are the same one, when selecting by key
+    same_item: Option<SameItem<T>>,
+    // true while rows are selected to match `selected`
+    syncing: Cell<bool>,
+    callbacks: RefCell<Vec<(usize, SelectionCallback<T>)>>,
+    next_callback_id: Cell<usize>,
+    handler_ids: RefCell<Option<(SignalHandlerId, SignalHandlerId, usize)>>
+}
+
+struct Selected<T> {
+    index: u32,
+    // only kept when selecting by key
+    item: Option<T>
+}
+
+impl<T, LM> ListSelection<T, LM>
+where T: 'static, LM: ListModel<T> + 'static {
+
+    /* Sets `mode` on the list box and takes in the rows already selected. */
+    pub fn new(bound_list_model: &BoundListModel<T, LM>, mode: gtk::SelectionMode) -> Self {
+        Self::build(bound_list_model, mode, None)
+    }
+
+    pub fn with_key<K, KF>(bound_list_model: &BoundListModel<T, LM>, mode: gtk::SelectionMode, key: KF) -> Self
+    where K: PartialEq, KF: Fn(&T) -> K + 'static {
+        Self::build(bound_list_model, mode, Some(Box::new(move |a: &T, b: &T| key(a) == key(b))))
+    }
+
+    fn build(bound_list_model: &BoundListModel<T, LM>,
+             mode: gtk::SelectionMode,
+             same_item: Option<SameItem<T>>) -> Self {
+        let list_box = bound_list_model.get_list_box().clone();
+        list_box.set_selection_mode(mode);
+        let list_model_gobj = bound_list_model.get_list_model_gobj().clone();
+        let gio_list_model = list_model_gobj.to_gio_list_model();
+        let inner = Rc::new(SelectionInner {
+            list_box,
+            list_model_gobj,
+            gio_list_model,
+            selected: RefCell::new(Vec::new()),
+            same_item,
+            syncing: Cell::new(false),
+            callbacks: RefCell::new(Vec::new()),
+            next_callback_id: Cell::new(0),
+            handler_ids: RefCell::new(None)
+        });
+        inner.rows_changed();
+
+        /* The list box handles `items-changed` first, as it connected when bound,
+           so its rows are up to date when the selection follows.
+           Both handlers read the user's model, so they are guarded. */
+        let weak_inner = Rc::downgrade(&inner);
+        let rows_handler_id = inner.list_box.connect_selected_rows_changed(move |_| {
+            if let Some(inner) = weak_inner.upgrade() {
+                panic_guard::guard("selected-rows-changed", || inner.rows_changed());
+            }
+        });
+        let weak_inner = Rc::downgrade(&inner);
+        let items_handler_id = inner.gio_list_model.connect_items_changed(move |_, position, removed, added| {
+            if let Some(inner) = weak_inner.upgrade() {
+                panic_guard::guard("items-changed", || inner.items_changed(position, removed, added));
+            }
+        });
+        let weak_inner = Rc::downgrade(&inner);
+        let updated_callback_id = inner.list_model_gobj.connect_item_updated(move |_, position| {
+            if let Some(inner) = weak_inner.upgrade() {
+                inner.item_updated(position);
+            }
+            true
+        });
+        *inner.handler_ids.borrow_mut() = Some((rows_handler_id, items_handler_id, updated_callback_id));
+        ListSelection { inner }
+    }
+
+    pub fn get_mode(&self) -> gtk::SelectionMode {
+        self.inner.list_box.get_selection_mode()
+    }
+
+    /* gtk unselects the rows the new mode can't keep. */
+    pub fn set_mode(&self, mode: gtk::SelectionMode) {
+        self.inner.list_box.set_selection_mode(mode);
+    }
+
+    /* In gtk::SelectionMode::Single and Browse, replaces the selected item. */
+    pub fn select(&self, index: u32) {
+        if let Some(row) = self.inner.list_box.get_row_at_index(index as i32) {
+            self.inner.list_box.select_row(&row);
+        }
+    }
+
+    pub fn unselect(&self, index: u32) {
+        if let Some(row) = self.inner.list_box.get_row_at_index(index as i32) {
+            self.inner.list_box.unselect_row(&row);
+        }
+    }
+
+    pub fn unselect_all(&self) {
+        self.inner.list_box.unselect_all();
+    }
+
+    pub fn is_selected(&self, index: u32) -> bool {
+        self.inner.selected.borrow().iter().any(|selected| selected.index == index)
+    }
+
+    pub fn selected_indices(&self) -> Vec<u32> {
+        self.inner.selected_indices()
+    }
+
+    pub fn selected_items(&self) -> Vec<T> {
+        self.inner.selected_pairs().into_iter().map(|(_, item)| item).collect()
+    }
+
+    /* `callback` gets the selected (index, item) pairs
+       whenever the selected indices change.
+       Keep the returned id to disconnect it. */
+    pub fn connect_selection_changed<F>(&self, callback: F) -> usize
+    where F: Fn(&[(u32, T)]) + 'static {
+        let id = self.inner.next_callback_id.get();
+        self.inner.next_callback_id.set(id + 1);
+        self.inner.callbacks.borrow_mut().push((id, Rc::new(callback)));
+        id
+    }
+
+    pub fn disconnect_selection_changed(&self, callback_id: usize) {
+        self.inner.callbacks.borrow_mut().retain(|&(id, _)| id != callback_id);
+    }
+}
+
+impl<T, LM> SelectionInner<T, LM>
+where T: 'static, LM: ListModel<T> + 'static {
+
+    fn selected_indices(&self) -> Vec<u32> {
+        self.selected.borrow().iter().map(|selected| selected.index).collect()
+    }
+
+    fn selected_pairs(&self) -> Vec<(u32, T)> {
+        self.selected_indices().into_iter()
+            .filter_map(|index| self.list_model_gobj.get().try_get_item(index).map(|item| (index, item)))
+            .collect()
+    }
+
+    fn snapshot(&self, index: u32) -> Selected<T> {
+        let item = match self.same_item {
+            Some(_) => self.list_model_gobj.get().try_get_item(index),
+            None => None
+        };
+        Selected { index, item }
+    }
+
+    /* Takes the selection from the rows, when the user or the
+       selection methods changed it. The selection changes gtk makes
+       while recreating rows are left to items_changed(). */
+    fn rows_changed(&self) {
+        if self.syncing.get() || self.list_model_gobj.is_emitting() {
+            return;
+        }
+        let mut indices: Vec<u32> = self.list_box.get_selected_rows().iter()
+            .map(|row| row.get_index())
+            .filter(|&index| index >= 0)
+            .map(|index| index as u32)
+            .collect();
+        indices.sort();
+        let selected = indices.into_iter().map(|index| self.snapshot(index)).collect();
+        self.set_selected(selected);
+    }
+
+    fn items_changed(&self, position: u32, removed: u32, added: u32) {
+        let mut new_selected: Vec<Selected<T>> = Vec::new();
+        let had_selection = {
+            // items are moved out, indices stay for set_selected() to compare
+            let mut old_selected = self.selected.borrow_mut();
+            for selected in old_selected.iter_mut() {
+                let item = selected.item.take();
+                if selected.index < position {
+                    new_selected.push(Selected { index: selected.index, item });
+                } else if selected.index >= position + removed {
+                    new_selected.push(Selected { index: selected.index - removed + added, item });
+                } else if let Some(index) = self.find_replacement(item.as_ref(), position, added) {
+                    new_selected.push(self.snapshot(index));
+                }
+            }
+            !old_selected.is_empty()
+        };
+        new_selected.sort_by_key(|selected| selected.index);
+        new_selected.dedup_by_key(|selected| selected.index);
+
+        let mode = self.list_box.get_selection_mode();
+        let n_items = self.list_model_gobj.get().get_n_items();
+        if mode == gtk::SelectionMode::Browse && had_selection && new_selected.is_empty() && n_items > 0 {
+            new_selected.push(self.snapshot(position.min(n_items - 1)));
+        }
+        if mode != gtk::SelectionMode::Multiple {
+            new_selected.truncate(1);
+        }
+
+        self.select_rows(&new_selected);
+        self.set_selected(new_selected);
+    }
+
+    /* Keeps the item of a selected row updated in place, to be found by key later. */
+    fn item_updated(&self, index: u32) {
+        if self.same_item.is_none() {
+            return;
+        }
+        let updated = self.snapshot(index);
+        for selected in self.selected.borrow_mut().iter_mut() {
+            if selected.index == index {
+                selected.item = updated.item;
+                return;
+            }
+        }
+    }
+
+    /* Index of an added item with the same key as a replaced selected one. */
+    fn find_replacement(&self, old_item: Option<&T>, position: u32, added: u32) -> Option<u32> {
+        let same_item = self.same_item.as_ref()?;
+        let old_item = old_item?;
+        (position..position + added).find(|&index| {
+            self.list_model_gobj.get().try_get_item(index).is_some_and(|item| same_item(old_item, &item))
+        })
+    }
+
+    /* Makes the selected rows match `selected`. */
+    fn select_rows(&self, selected: &[Selected<T>]) {
+        let is_selected = |index: i32| selected.iter().any(|selected| selected.index as i32 == index);
+        self.syncing.set(true);
+        for row in self.list_box.get_selected_rows() {
+            if !is_selected(row.get_index()) {
+                self.list_box.unselect_row(&row);
+            }
+        }
+        for selected in selected {
+            if let Some(row) = self.list_box.get_row_at_index(selected.index as i32) {
+                if !row.is_selected() {
+                    self.list_box.select_row(&row);
+                }
+            }
+        }
+        self.syncing.set(false);
+    }
+
+    fn set_selected(&self, selected: Vec<Selected<T>>) {
+        let changed = {
+            let mut current = self.selected.borrow_mut();
+            let changed = current.iter().map(|selected| selected.index).ne(selected.iter().map(|selected| selected.index));
+            *current = selected;
+            changed
+        };
+        if changed {
+            let pairs = self.selected_pairs();
+            let callbacks: Vec<_> = self.callbacks.borrow().iter()
+                .map(|(_, callback)| callback.clone())
+                .collect();
+            for callback in callbacks {
+                panic_guard::guard("selection-changed", || callback(&pairs));
+            }
+        }
+    }
+}
+
+impl<T, LM> Drop for SelectionInner<T, LM>
+where T: 'static, LM: ListModel<T> + 'static {
+    fn drop(&mut self) {
+        if let Some((rows_handler_id, items_handler_id, updated_callback_id)) = self.handler_ids.borrow_mut().take() {
+            self.list_box.disconnect(rows_handler_id);
+            self.gio_list_model.disconnect(items_handler_id);
+            self.list_model_gobj.disconnect_item_updated(updated_callback_id);
+        }
+    }
+}
+
+impl<T, LM> Clone for ListSelection<T, LM>
+where T: 'static, LM: ListModel<T> + 'static {
+    fn clone(&self) -> Self {
+        ListSelection { inner: self.inner.clone() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    extern crate gtk;
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use self::gtk::prelude::*;
+    use super::ListSelection;
+    use super::super::public_interface::{gtk_list_box_bind_model, BoundListModel};
+    use super::super::vec_list_model::VecListModel;
+    use super::super::test_helpers::gtk_test;
+
+    fn bind(items: Vec<&'static str>) -> (VecListModel<&'static str>, BoundListModel<&'static str, VecListModel<&'static str>>) {
+        let model = VecListModel::from(items);
+        let bound_list_model = gtk_list_box_bind_model(&gtk::ListBox::new(), model.clone(), |item| {
+            gtk::Label::new(Some(*item)).upcast()
+        });
+        (model, bound_list_model)
+    }
+
+    #[test]
+    /* Selected indices move with the items around them. */
+    fn follows_model_changes() {
+        gtk_test(|| {
+            let (model, bound_list_model) = bind(vec!["a", "b", "c", "d"]);
+            let selection = ListSelection::new(&bound_list_model, gtk::SelectionMode::Multiple);
+            let changes = Rc::new(RefCell::new(Vec::new()));
+            let changes_clone = changes.clone();
+            let callback_id = selection.connect_selection_changed(move |pairs| changes_clone.borrow_mut().push(pairs.to_vec()));
+
+            selection.select(1);
+            selection.select(3);
+            model.insert(0, "z");
+            assert_eq!(selection.selected_indices(), vec![2, 4]);
+            model.remove(2);
+            assert_eq!(selection.selected_indices(), vec![3]);
+            assert_eq!(selection.selected_items(), vec!["d"]);
+            assert!(bound_list_model.get_list_box().get_row_at_index(3).unwrap().is_selected());
+            assert_eq!(*changes.borrow(), vec![
+                vec![(1, "b")],
+                vec![(1, "b"), (3, "d")],
+                vec![(2, "b"), (4, "d")],
+                vec![(3, "d")]
+            ]);
+
+            selection.disconnect_selection_changed(callback_id);
+            selection.select(0);
+            assert_eq!(selection.selected_indices(), vec![0, 3]);
+            assert_eq!(changes.borrow().len(), 4);
+        });
+    }
+
+    #[test]
+    /* A replaced item stays selected if an added one has its key. */
+    fn keyed_selection_survives_replacement() {
+        gtk_test(|| {
+            let (model, bound_list_model) = bind(vec!["a", "b", "c"]);
+            let selection = ListSelection::with_key(&bound_list_model, gtk::SelectionMode::Single, |item: &&str| *item);
+            selection.select(1);
+            model.splice(0..3, vec!["b", "c"]);
+            assert_eq!(selection.selected_indices(), vec![0]);
+            drop(selection);
+
+            let plain_selection = ListSelection::new(&bound_list_model, gtk::SelectionMode::Single);
+            assert_eq!(plain_selection.selected_indices(), vec![0]);
+            model.splice(0..2, vec!["b", "c"]);
+            assert!(plain_selection.selected_indices().is_empty());
+        });
+    }
+
+    #[test]
+    /* In browse mode, the item taking the place of the removed one is selected. */
+    fn browse_keeps_a_selection() {
+        gtk_test(|| {
+            let (model, bound_list_model) = bind(vec!["a", "b", "c"]);
+            let selection = ListSelection::new(&bound_list_model, gtk::SelectionMode::Browse);
+            selection.select(2);
+            model.remove(2);
+            assert_eq!(selection.selected_items(), vec!["b"]);
+        });
+    }
+}
@@ -0,0 +1,186 @@
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use super::public_interface::ListModel;
+use super::change_notifier::{ChangeNotifier, HandlerId};
+
+/* Presents each item of a child model as `map(item)`,
+   eg: a domain model shown through a view type made for the rows.
+   Changes of a notifying child are passed on as they are,
+   so the same child can back several differently mapped lists.
+
+   Made with `with_cache`, mapped items are kept
+   until the child reports them as changed. Clones share the same state. */
+pub struct MapListModel<T, U, LM, F>
+where LM: ListModel<T>, F: Fn(T) -> U {
+    inner: Rc<MapInner<T, U, LM, F>>
+}
+
+struct MapInner<T, U, LM, F>
+where LM: ListModel<T>, F: Fn(T) -> U {
+    child: LM,
+    map: F,
+    cache: Option<MapCache<U>>,
+    change_notifier: ChangeNotifier,
+    child_handler_id: Cell<Option<HandlerId>>,
+    phantom: PhantomData<T>
+}
+
+struct MapCache<U> {
+    items: RefCell<Vec<Option<U>>>,
+    // U::clone, only known to be there when the cache was asked for
+    clone_item: fn(&U) -> U
+}
+
+impl<T, U, LM, F> MapListModel<T, U, LM, F>
+where T: 'static, U: 'static, LM: ListModel<T> + 'static, F: Fn(T) -> U + 'static {
+
+    pub fn new(child: LM, map: F) -> Self {
+        Self::build(child, map, None)
+    }
+
+    pub fn with_cache(child: LM, map: F) -> Self
+    where U: Clone {
+        Self::build(child, map, Some(MapCache {
+            items: RefCell::new(Vec::new()),
+            clone_item: U::clone
+        }))
+    }
+
+    fn build(child: LM, map: F, cache: Option<MapCache<U>>) -> Self {
+        let inner = Rc::new(MapInner {
+            child,
+            map,
+            cache,
+            change_notifier: ChangeNotifier::new(),
+            child_handler_id: Cell::new(None),
+            phantom: PhantomData
+        });
+        if let Some(child_notifier) = inner.child.get_change_notifier() {
+            let weak_inner = Rc::downgrade(&inner);
+            let handler_id = child_notifier.connect(move |position, removed, added| {
+                if let Some(inner) = weak_inner.upgrade() {
+                    inner.child_items_changed(position, removed, added);
+                }
+            });
+            inner.child_handler_id.set(Some(handler_id));
+        }
+        MapListModel { inner }
+    }
+
+    pub fn get_child(&self) -> &LM {
+        &self.inner.child
+    }
+}
+
+impl<T, U, LM, F> MapInner<T, U, LM, F>
+where LM: ListModel<T>, F: Fn(T) -> U {
+
+    fn try_get_item(&self, index: u32) -> Option<U> {
+        let cache = match self.cache {
+            Some(ref cache) => cache,
+            None => return self.child.try_get_item(index).map(&self.map)
+        };
+        if let Some(Some(item)) = cache.items.borrow().get(index as usize) {
+            return Some((cache.clone_item)(item));
+        }
+        let item = (self.map)(self.child.try_get_item(index)?);
+        let mut items = cache.items.borrow_mut();
+        if items.len() <= index as usize {
+            items.resize_with(index as usize + 1, || None);
+        }
+        items[index as usize] = Some((cache.clone_item)(&item));
+        Some(item)
+    }
+
+    fn child_items_changed(&self, position: u32, removed: u32, added: u32) {
+        if let Some(ref cache) = self.cache {
+            let mut items = cache.items.borrow_mut();
+            let position = position as usize;
+            if position < items.len() {
+                let removed_end = (position + removed as usize).min(items.len());
+                items.splice(position..removed_end, (0..added).map(|_| None));
+            }
+        }
+        self.change_notifier.emit(position, removed, added);
+    }
+}
+
+impl<T, U, LM, F> Drop for MapInner<T, U, LM, F>
+where LM: ListModel<T>, F: Fn(T) -> U {
+    fn drop(&mut self) {
+        if let (Some(handler_id), Some(child_notifier)) = (self.child_handler_id.get(), self.child.get_change_notifier()) {
+            child_notifier.disconnect(handler_id);
+        }
+    }
+}
+
+impl<T, U, LM, F> Clone for MapListModel<T, U, LM, F>
+where LM: ListModel<T>, F: Fn(T) -> U {
+    fn clone(&self) -> Self {
+        MapListModel { inner: self.inner.clone() }
+    }
+}
+
+impl<T, U, LM, F> ListModel<U> for MapListModel<T, U, LM, F>
+where LM: ListModel<T>, F: Fn(T) -> U {
+    fn get_n_items(&self) -> u32 {
+        self.inner.child.get_n_items()
+    }
+
+    fn get_item(&self, index: u32) -> U {
+        match self.inner.try_get_item(index) {
+            Some(item) => item,
+            None => (self.inner.map)(self.inner.child.get_item(index))
+        }
+    }
+
+    fn try_get_item(&self, index: u32) -> Option<U> {
+        self.inner.try_get_item(index)
+    }
+
+    fn get_change_notifier(&self) -> Option<&ChangeNotifier> {
+        Some(&self.inner.change_notifier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::rc::Rc;
+    use std::cell::Cell;
+    use super::MapListModel;
+    use super::super::vec_list_model::VecListModel;
+    use super::super::test_helpers::{record_emissions, list_items as items};
+
+    #[test]
+    /* Child changes come through unchanged. */
+    fn maps_items_and_forwards_changes() {
+        let child = VecListModel::from(vec![1, 2]);
+        let map_model = MapListModel::new(child.clone(), |item: i32| format!("#{}", item));
+        let emissions = record_emissions(&map_model);
+        child.push(3);
+        child.remove(0);
+        assert_eq!(items(&map_model), vec![String::from("#2"), String::from("#3")]);
+        assert_eq!(*emissions.borrow(), vec![(2, 0, 1), (0, 1, 0)]);
+    }
+
+    #[test]
+    /* Cached items are mapped once, until the child replaces them. */
+    fn cache_maps_once() {
+        let map_calls = Rc::new(Cell::new(0));
+        let map_calls_clone = map_calls.clone();
+        let child = VecListModel::from(vec![1, 2, 3]);
+        let map_model = MapListModel::with_cache(child.clone(), move |item: i32| {
+            map_calls_clone.set(map_calls_clone.get() + 1);
+            item * 10
+        });
+        assert_eq!(items(&map_model), vec![10, 20, 30]);
+        assert_eq!(items(&map_model), vec![10, 20, 30]);
+        assert_eq!(map_calls.get(), 3);
+
+        child.splice(1..2, vec![4, 5]);
+        assert_eq!(items(&map_model), vec![10, 40, 50, 30]);
+        assert_eq!(map_calls.get(), 5);
+    }
+}
@@ -0,0 +1,124 @@
+extern crate gtk;
+extern crate glib;
+
+use self::gtk::prelude::*;
+use self::glib::object::IsA;
+use super::public_interface::{ListModel, RowFactory, BoundModel, ListBoxModelExt, FlowBoxModelExt,
+                              gtk_list_box_bind_model_with_factory, gtk_flow_box_bind_model_with_factory};
+
+/* The widgets a model can be bound to, gtk::ListBox and gtk::FlowBox,
+   so that BoundModel is written once for both.
+   The module isn't exported: the trait can't be implemented
+   or called outside this crate, and its methods don't
+   clash with the extension traits of the widgets. */
+pub trait ModelWidget: IsA<gtk::Widget> + IsA<glib::Object> + glib::ObjectExt + Clone + 'static {
+    /* gtk_list_box_bind_model_with_factory() or gtk_flow_box_bind_model_with_factory(). */
+    fn bind_new_model<T, LM, WC>(&self, list_model: LM, row_factory: WC) -> BoundModel<Self, T, LM>
+    where T:'static, LM: ListModel<T>+'static, WC: RowFactory<T>+'static;
+
+    /* See `ListBoxModelExt::unbind_model()`. */
+    fn unbind_bound_model(&self);
+
+    /* The row or child at `index`. */
+    fn get_item_widget(&self, index: i32) -> Option<gtk::Widget>;
+
+    /* Tells gtk that the row or child at `index` changed,
+       so its header, sorting and filtering are asked for again. */
+    fn item_widget_changed(&self, index: i32);
+
+    fn get_selected_indices(&self) -> Vec<i32>;
+
+    fn select_index(&self, index: i32);
+
+    /* The vertical adjustment of the scrolled window showing the widget, if any. */
+    fn get_scroll_adjustment(&self) -> Option<gtk::Adjustment>;
+
+    /* The index of a row or child at `y`. */
+    fn get_index_at_y(&self, y: i32) -> Option<i32>;
+}
+
+impl ModelWidget for gtk::ListBox {
+    fn bind_new_model<T, LM, WC>(&self, list_model: LM, row_factory: WC) -> BoundModel<Self, T, LM>
+    where T:'static, LM: ListModel<T>+'static, WC: RowFactory<T>+'static {
+        gtk_list_box_bind_model_with_factory(self, list_model, row_factory)
+    }
+
+    fn unbind_bound_model(&self) {
+        ListBoxModelExt::unbind_model(self);
+    }
+
+    fn get_item_widget(&self, index: i32) -> Option<gtk::Widget> {
+        self.get_row_at_index(index).map(|row| row.upcast())
+    }
+
+    fn item_widget_changed(&self, index: i32) {
+        if let Some(row) = self.get_row_at_index(index) {
+            row.changed();
+        }
+    }
+
+    fn get_selected_indices(&self) -> Vec<i32> {
+        self.get_selected_rows().iter().map(|row| row.get_index()).collect()
+    }
+
+    fn select_index(&self, index: i32) {
+        if let Some(row) = self.get_row_at_index(index) {
+            self.select_row(&row);
+        }
+    }
+
+    fn get_scroll_adjustment(&self) -> Option<gtk::Adjustment> {
+        self.get_adjustment()
+    }
+
+    fn get_index_at_y(&self, y: i32) -> Option<i32> {
+        self.get_row_at_y(y).map(|row| row.get_index())
+    }
+}
+
+impl ModelWidget for gtk::FlowBox {
+    fn bind_new_model<T, LM, WC>(&self, list_model: LM, row_factory: WC) -> BoundModel<Self, T, LM>
+    where T:'static, LM: ListModel<T>+'static, WC: RowFactory<T>+'static {
+        gtk_flow_box_bind_model_with_factory(self, list_model, row_factory)
+    }
+
+    fn unbind_bound_model(&self) {
+        FlowBoxModelExt::unbind_model(self);
+    }
+
+    fn get_item_widget(&self, index: i32) -> Option<gtk::Widget> {
+        self.get_child_at_index(index).map(|child| child.upcast())
+    }
+
+    fn item_widget_changed(&self, index: i32) {
+        if let Some(child) = self.get_child_at_index(index) {
+            child.changed();
+        }
+    }
+
+    fn get_selected_indices(&self) -> Vec<i32> {
+        self.get_selected_children().iter().map(|child| child.get_index()).collect()
+    }
+
+    fn select_index(&self, index: i32) {
+        if let Some(child) = self.get_child_at_index(index) {
+            self.select_child(&child);
+        }
+    }
+
+    // unlike a list box, a flow box doesn't keep the adjustment of its viewport
+    fn get_scroll_adjustment(&self) -> Option<gtk::Adjustment> {
+        self.get_parent()?.downcast::<gtk::Viewport>().ok()?.get_vadjustment()
+    }
+
+    // the first child on the line at `y`
+    fn get_index_at_y(&self, y: i32) -> Option<i32> {
+        self.get_children().into_iter()
+            .filter_map(|child| child.downcast::<gtk::FlowBoxChild>().ok())
+            .find(|child| {
+                let allocation = child.get_allocation();
+                allocation.y <= y && y < allocation.y + allocation.height
+            })
+            .map(|child| child.get_index())
+    }
+}
@@ -0,0 +1,91 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+/* Unwinding out of an `extern "C"` function is undefined behaviour,
+   so every callback gtk/GObject makes into this crate runs its body
+   through `guard()`. A panic is handed to the panic handler
+   and the callback returns a harmless fallback instead. */
+
+type PanicHandler = Rc<dyn Fn(&str, &str)>;
+
+thread_local! {
+    static PANIC_HANDLER: RefCell<Option<PanicHandler>> = RefCell::new(None);
+}
+
+/* Called with the name of the callback which panicked and the panic message.
+   Without one, both are printed to stderr.
+   gtk runs on a single thread, so the handler is set per thread. */
+pub fn set_panic_handler<F>(handler: F)
+where F: Fn(&str, &str) + 'static {
+    PANIC_HANDLER.with(|panic_handler| {
+        *panic_handler.borrow_mut() = Some(Rc::new(handler));
+    });
+}
+
+/* Runs `f`, returning None if it panicked. */
+pub fn guard<R, F>(callback_name: &str, f: F) -> Option<R>
+where F: FnOnce() -> R {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => Some(result),
+        Err(payload) => {
+            report_panic(callback_name, &panic_message(&*payload));
+            None
+        }
+    }
+}
+
+fn report_panic(callback_name: &str, message: &str) {
+    let handler = PANIC_HANDLER.with(|panic_handler| panic_handler.borrow().clone());
+    // the handler itself must not unwind into C either
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        match handler {
+            Some(handler) => handler(callback_name, message),
+            None => eprintln!("gtk-rs-list-box-model: panic in {}: {}", callback_name, message)
+        }
+    }));
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        String::from(*message)
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic payload")
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    #[test]
+    fn results_pass_through() {
+        assert_eq!(super::guard("test", || 42), Some(42));
+    }
+
+    #[test]
+    /* The panic is caught and reported to the handler. */
+    fn panics_are_reported() {
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let reports_clone = reports.clone();
+        super::set_panic_handler(move |callback_name, message| {
+            reports_clone.borrow_mut().push((String::from(callback_name), String::from(message)));
+        });
+        let result: Option<u32> = super::guard("get_item", || panic!("Index out of bounds: {}", 3));
+        assert_eq!(result, None);
+        assert_eq!(*reports.borrow(), vec![(String::from("get_item"), String::from("Index out of bounds: 3"))]);
+    }
+
+    #[test]
+    /* A panicking handler doesn't escape either. */
+    fn panicking_handler_is_contained() {
+        super::set_panic_handler(|_, _| panic!("handler panicked"));
+        let result: Option<()> = super::guard("dispose", || panic!("dispose panicked"));
+        assert_eq!(result, None);
+    }
+}
@@ -0,0 +1,140 @@
+/* items-changed emissions held back while a ChangeNotifier is frozen,
+   merged into as few as possible.
+
+   Each region stands for `removed` items of the list as it was
+   when the freeze started, replaced by the `added` items now at
+   [position, position + added). Regions are kept sorted and
+   apart from each other, items between them are unchanged. */
+#[derive(Default)]
+pub struct PendingChanges {
+    regions: Vec<Region>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Region {
+    position: u32,
+    removed: u32,
+    added: u32
+}
+
+impl PendingChanges {
+
+    pub fn new() -> Self {
+        PendingChanges { regions: Vec::new() }
+    }
+
+    /* Takes in a change, given in positions of the list as it is now. */
+    pub fn add(&mut self, position: u32, removed: u32, added: u32) {
+        let change_end = position + removed;
+        // regions overlapping or touching [position, change_end)
+        let first = self.regions.iter()
+            .position(|region| region.position + region.added >= position)
+            .unwrap_or(self.regions.len());
+        let last = first + self.regions[first..].iter()
+            .take_while(|region| region.position <= change_end)
+            .count();
+        let merged = &self.regions[first..last];
+
+        let start = merged.first().map_or(position, |region| region.position.min(position));
+        let end = merged.last().map_or(change_end, |region| (region.position + region.added).max(change_end));
+        let merged_removed: u32 = merged.iter().map(|region| region.removed).sum();
+        let merged_added: u32 = merged.iter().map(|region| region.added).sum();
+        let new_region = Region {
+            position: start,
+            // the unchanged items in the span were part of the old list
+            removed: merged_removed + (end - start - merged_added),
+            added: end - start - removed + added
+        };
+
+        for region in self.regions[last..].iter_mut() {
+            region.position = region.position + added - removed;
+        }
+        let replacement = if new_region.removed == 0 && new_region.added == 0 { None } else { Some(new_region) };
+        self.regions.splice(first..last, replacement);
+    }
+
+    /* The merged changes as (position, removed, added), to be emitted in order. */
+    pub fn take(&mut self) -> Vec<(u32, u32, u32)> {
+        self.regions.drain(..).map(|region| (region.position, region.removed, region.added)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::PendingChanges;
+
+    /* Applies the changes to a list of numbered items,
+       new items being numbered from 1000 on. */
+    fn apply(items: &mut Vec<u32>, next_item: &mut u32, position: u32, removed: u32, added: u32) {
+        let new_items: Vec<u32> = (0..added).map(|offset| *next_item + offset).collect();
+        *next_item += added;
+        items.splice(position as usize..(position + removed) as usize, new_items);
+    }
+
+    /* Replaying the merged changes on the old list, taking the added items
+       from the new one, must give the new list. */
+    fn check(initial_len: u32, changes: &[(u32, u32, u32)]) -> Vec<(u32, u32, u32)> {
+        let old_items: Vec<u32> = (0..initial_len).collect();
+        let mut new_items = old_items.clone();
+        let mut next_item = 1000;
+        let mut pending = PendingChanges::new();
+        for &(position, removed, added) in changes {
+            apply(&mut new_items, &mut next_item, position, removed, added);
+            pending.add(position, removed, added);
+        }
+        let merged = pending.take();
+        let mut replayed = old_items.clone();
+        for &(position, removed, added) in merged.iter() {
+            let range = position as usize..(position + added) as usize;
+            replayed.splice(position as usize..(position + removed) as usize, new_items[range].iter().cloned());
+        }
+        assert_eq!(replayed, new_items, "{:?} merged into {:?}", changes, merged);
+        merged
+    }
+
+    #[test]
+    fn overlapping_changes_are_merged() {
+        assert_eq!(check(10, &[(2, 0, 1), (3, 0, 1)]), vec![(2, 0, 2)]);
+        assert_eq!(check(10, &[(2, 0, 3), (3, 1, 0)]), vec![(2, 0, 2)]);
+        assert_eq!(check(10, &[(2, 2, 0), (1, 2, 1)]), vec![(1, 4, 1)]);
+        assert_eq!(check(10, &[(4, 1, 1), (2, 5, 0)]), vec![(2, 5, 0)]);
+    }
+
+    #[test]
+    /* Changes far apart stay apart, with positions
+       adjusted for the changes in front of them. */
+    fn distant_changes_stay_apart() {
+        assert_eq!(check(10, &[(8, 1, 0), (0, 0, 2)]), vec![(0, 0, 2), (10, 1, 0)]);
+        assert_eq!(check(10, &[(1, 0, 1), (9, 1, 1), (5, 1, 0)]), vec![(1, 0, 1), (5, 1, 0), (8, 1, 1)]);
+    }
+
+    #[test]
+    /* Adding and removing the same item leaves nothing to tell. */
+    fn cancelling_changes_vanish() {
+        assert_eq!(check(5, &[(2, 0, 1), (2, 1, 0)]), vec![]);
+    }
+
+    #[test]
+    /* Pseudo-random sequences of changes, checked by replaying. */
+    fn random_changes_replay() {
+        let mut seed: u32 = 12345;
+        let mut next = |bound: u32| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % bound
+        };
+        for _ in 0..500 {
+            let mut len = 1 + next(15);
+            let initial_len = len;
+            let mut changes = Vec::new();
+            for _ in 0..1 + next(8) {
+                let position = next(len + 1);
+                let removed = next(len - position + 1);
+                let added = next(4);
+                changes.push((position, removed, added));
+                len = len - removed + added;
+            }
+            check(initial_len, &changes);
+        }
+    }
+}
@@ -2,46 +2,968 @@ extern crate gtk;
 extern crate gtk_sys as gtk_ffi;
 extern crate gobject_sys as gobject_ffi;
 extern crate glib_sys as glib_ffi;
+extern crate gio_sys as gio_ffi;
 extern crate glib;
+extern crate gdk;
 
 use self::glib::translate::*;
+use self::gtk::prelude::*;
+use self::glib::signal::SignalHandlerId;
+use self::glib::object::IsA;
+use std::ptr;
 use std::mem;
+use std::rc::Rc;
+use std::cell::Cell;
+use std::hash::Hash;
+use std::collections::HashSet;
+use std::ops::Deref;
 use super::list_model_gobject::ListModelGObject;
 use super::container_gobject::ContainerGObject;
+use super::GObjectPtrWrapper;
+use super::gio_list_model::GioListModel;
+use super::change_notifier::{ChangeNotifier, HandlerId};
+use super::item_cache::ItemCacheMode;
+use super::panic_guard;
+use super::vec_list_model::VecListModel;
+use super::model_widget::ModelWidget;
 
 
 pub trait ListModel<T> {
     fn get_n_items(&self) -> u32;
     fn get_item(&self, index: u32) -> T;
+
+    /* Like get_item, but None instead of a panic when there is
+       no item at `index`, eg: the data shrank since get_n_items was asked.
+       ListModelGObject only calls this one, and gives NULL to gtk for None. */
+    fn try_get_item(&self, index: u32) -> Option<T> {
+        if index < self.get_n_items() {
+            Some(self.get_item(index))
+        } else {
+            None
+        }
+    }
+
+    /* Models which change after binding return the ChangeNotifier
+       they emit on, and ListModelGObject forwards its emissions to gtk. */
+    fn get_change_notifier(&self) -> Option<&ChangeNotifier> {
+        None
+    }
+
+    /* Calls `callback(position, removed, added)` whenever the model changes,
+       with the same arguments gtk gets in `items-changed`.
+       None for models which don't notify. */
+    fn connect_items_changed<F>(&self, callback: F) -> Option<HandlerId>
+    where F: Fn(u32, u32, u32) + 'static, Self: Sized {
+        self.get_change_notifier().map(|notifier| notifier.connect(callback))
+    }
+
+    fn disconnect_items_changed(&self, handler_id: HandlerId) {
+        if let Some(notifier) = self.get_change_notifier() {
+            notifier.disconnect(handler_id);
+        }
+    }
+}
+
+/* For models whose items carry a stable key, eg: a job id,
+   to find an item's position without going through every item.
+   The default index_of() does go through them,
+   models keeping a key to index map override it. */
+pub trait KeyedListModel<K, T>: ListModel<T>
+where K: PartialEq {
+    fn key_of(&self, item: &T) -> K;
+
+    fn index_of(&self, key: &K) -> Option<u32> {
+        (0..self.get_n_items()).find(|&index| {
+            self.try_get_item(index).is_some_and(|item| self.key_of(&item) == *key)
+        })
+    }
+}
+
+/* Makes the widgets of the rows for the `_with_factory` variants
+   of gtk_list_box_bind_model and friends, which take it instead of a closure.
+   Any `Fn(&T) -> gtk::Widget` closure is one which only creates them. */
+pub trait RowFactory<T: ?Sized> {
+    fn create(&self, item: &T) -> gtk::Widget;
+
+    /* Makes `widget`, which create() made for another item, show `item` instead.
+       false if it can't, the default, and the row is created again. */
+    fn update(&self, _widget: &gtk::Widget, _item: &T) -> bool {
+        false
+    }
+
+    /* Called when `widget`, made by create(), is destroyed,
+       eg: to disconnect the handlers create() connected elsewhere. */
+    fn teardown(&self, _widget: &gtk::Widget) {}
+}
+
+impl<T, F> RowFactory<T> for F
+where T: ?Sized, F: Fn(&T) -> gtk::Widget {
+    fn create(&self, item: &T) -> gtk::Widget {
+        self(item)
+    }
+}
+
+/* Handle to a model bound by `gtk_list_box_bind_model` or `gtk_flow_box_bind_model`,
+   used as BoundListModel and BoundFlowBoxModel.
+   Holds a reference to the widget and to the ListModelGObject given to gtk,
+   so the model stays reachable after binding
+   and gtk can be told when it changes. */
+pub struct BoundModel<W, T, LM> where W: ModelWidget, T:'static, LM: ListModel<T>+'static {
+    widget: W,
+    list_model_gobj: GObjectPtrWrapper<ListModelGObject<T, LM>>
+}
+
+/* Handle to a model bound to a gtk::ListBox. */
+pub type BoundListModel<T, LM> = BoundModel<gtk::ListBox, T, LM>;
+
+/* Handle to a model bound to a gtk::FlowBox. */
+pub type BoundFlowBoxModel<T, LM> = BoundModel<gtk::FlowBox, T, LM>;
+
+impl<W, T, LM> BoundModel<W, T, LM> where W: ModelWidget, T:'static, LM: ListModel<T>+'static {
+
+    pub(crate) fn get_list_model_gobj(&self) -> &GObjectPtrWrapper<ListModelGObject<T, LM>> {
+        &self.list_model_gobj
+    }
+
+    pub fn model(&self) -> &LM {
+        self.list_model_gobj.get()
+    }
+
+    /* The object gtk is bound to, eg: to hand the same model to other gtk-rs APIs. */
+    pub fn get_gio_list_model(&self) -> GioListModel {
+        self.list_model_gobj.to_gio_list_model()
+    }
+
+    pub fn get_item_cache_mode(&self) -> ItemCacheMode {
+        self.list_model_gobj.get_item_cache_mode()
+    }
+
+    /* Whether asking the model twice for the same row
+       gives the same ContainerGObject. Defaults to ItemCacheMode::Weak. */
+    pub fn set_item_cache_mode(&self, item_cache_mode: ItemCacheMode) {
+        self.list_model_gobj.set_item_cache_mode(item_cache_mode);
+    }
+
+    /* Whether this is still the model the widget shows. */
+    pub fn is_bound(&self) -> bool {
+        is_bound_to(&self.widget, &self.list_model_gobj)
+    }
+
+    /* Removes the rows or children and lets go of the model, see `ListBoxModelExt::unbind_model()`.
+       When this returns, the widget creator has been dropped, and so has
+       the ListModelGObject unless other handles, eg: a ListSelection, still hold it.
+       Leaves the widget alone if another model was bound since. */
+    pub fn unbind(self) {
+        if self.is_bound() {
+            self.widget.unbind_bound_model();
+        }
+    }
+
+    /* Tell gtk that `removed` items starting at `position`
+       were replaced by `added` new ones.
+       Only the rows in that range are recreated.
+       For notifying models, this goes through the model's ChangeNotifier,
+       so its other subscribers hear about it too. */
+    pub fn items_changed(&self, position: u32, removed: u32, added: u32) {
+        match self.model().get_change_notifier() {
+            Some(notifier) => notifier.emit(position, removed, added),
+            None => self.list_model_gobj.items_changed(position, removed, added)
+        }
+    }
+
+    /* Tell gtk that the item at `position` changed in place,
+       eg: through a RefCell inside it.
+       `RowFactory::update()` refreshes the existing rows, which keep
+       their focus and state, and their headers are asked for again.
+       When a factory can't, the rows are recreated with items_changed(position, 1, 1).
+       For notifying models, this goes through `ChangeNotifier::emit_item_updated()`,
+       so the other widgets bound to the model and its other subscribers hear about it,
+       and a frozen notifier holds it back as a replacement. */
+    pub fn item_updated(&self, position: u32) {
+        match self.model().get_change_notifier() {
+            Some(notifier) => notifier.emit_item_updated(position),
+            None => self.list_model_gobj.item_updated(position)
+        }
+    }
+
+    /* Unbinds this model and binds `list_model` in its place. */
+    pub fn rebind<LM2, F>(self, list_model: LM2, widget_creator: F) -> BoundModel<W, T, LM2>
+    where LM2: ListModel<T>+'static, F: Fn(&T) -> gtk::Widget+'static {
+        self.rebind_with_factory(list_model, widget_creator)
+    }
+
+    pub fn rebind_with_factory<LM2, WC>(self, list_model: LM2, row_factory: WC) -> BoundModel<W, T, LM2>
+    where LM2: ListModel<T>+'static, WC: RowFactory<T>+'static {
+        let widget = self.widget.clone();
+        self.unbind();
+        widget.bind_new_model(list_model, row_factory)
+    }
+
+    /* Like rebind(), keeping the selection and the scroll position:
+       rows or children of items whose `key_of` was selected are selected again,
+       and the widget stays scrolled to the item at its top. */
+    pub fn rebind_by_key<K, KF, LM2, F>(self, list_model: LM2, widget_creator: F, key_of: KF)
+                                        -> BoundModel<W, T, LM2>
+    where K: Eq+Hash, KF: Fn(&T)->K, LM2: ListModel<T>+'static, F: Fn(&T) -> gtk::Widget+'static {
+        self.rebind_by_key_with_factory(list_model, widget_creator, key_of)
+    }
+
+    pub fn rebind_by_key_with_factory<K, KF, LM2, WC>(self, list_model: LM2, row_factory: WC, key_of: KF)
+                                                      -> BoundModel<W, T, LM2>
+    where K: Eq+Hash, KF: Fn(&T)->K, LM2: ListModel<T>+'static, WC: RowFactory<T>+'static {
+        let widget = self.widget.clone();
+        let key_at = |index: i32| item_at(&self.list_model_gobj, index).map(|(_, item)| key_of(item.get()));
+        let selected_keys = widget.get_selected_indices().into_iter()
+            .filter_map(key_at)
+            .collect::<HashSet<K>>();
+        // The key of the topmost visible row and how far it is scrolled past.
+        let adjustment = widget.get_scroll_adjustment();
+        let scroll_anchor = adjustment.as_ref().and_then(|adjustment| {
+            let index = widget.get_index_at_y(adjustment.get_value() as i32)?;
+            let key = key_at(index)?;
+            let item_widget = widget.get_item_widget(index)?;
+            Some((key, adjustment.get_value() - item_widget.get_allocation().y as f64))
+        });
+        self.unbind();
+
+        let bound_model = widget.bind_new_model(list_model, row_factory);
+        let mut anchor_widget = None;
+        for index in 0..bound_model.model().get_n_items() {
+            let key = match bound_model.model().try_get_item(index) {
+                Some(item) => key_of(&item),
+                None => continue
+            };
+            if selected_keys.contains(&key) {
+                widget.select_index(index as i32);
+            }
+            if scroll_anchor.as_ref().is_some_and(|(anchor_key, _)| *anchor_key == key) {
+                anchor_widget = widget.get_item_widget(index as i32);
+            }
+        }
+        if let (Some(adjustment), Some(anchor_widget), Some((_, offset))) = (adjustment, anchor_widget, scroll_anchor) {
+            // The new rows have no position until the widget is allocated again.
+            let handler_id = Rc::new(Cell::new(None));
+            let handler_id_clone = handler_id.clone();
+            handler_id.set(Some(widget.connect_size_allocate(move |widget, _| {
+                adjustment.set_value(anchor_widget.get_allocation().y as f64 + offset);
+                if let Some(handler_id) = handler_id_clone.take() {
+                    widget.disconnect(handler_id);
+                }
+            })));
+        }
+        bound_model
+    }
 }
 
-/* Shall I turn this into a trait and implement it on gtk::ListBox ? */
-pub fn gtk_list_box_bind_model<T, LM, WC>(list_box: &gtk::ListBox,
-                                          list_model: LM,
-                                          widget_creator: WC )
-where T:'static, LM: ListModel<T>+'static, WC: Fn(&T)->gtk::Widget   {
+impl<T, LM> BoundListModel<T, LM> where T:'static, LM: ListModel<T>+'static {
+
+    pub fn get_list_box(&self) -> &gtk::ListBox {
+        &self.widget
+    }
+
+    /* `callback(index, item)` when a row is activated, eg: double-clicked.
+       Keeps working after rebinding to a model of the same type. */
+    pub fn connect_item_activated<F>(&self, callback: F) -> SignalHandlerId
+    where F: Fn(u32, &T) + 'static {
+        self.widget.connect_row_activated(move |list_box, row| {
+            panic_guard::guard("item-activated", || {
+                if let Some((index, item)) = bound_item_at::<T, LM, _>(list_box, row.get_index()) {
+                    callback(index, item.get());
+                }
+            });
+        })
+    }
 
-    extern "C" fn create_widget<T, WC> (item: *mut gobject_ffi::GObject, user_data: glib_ffi::gpointer)
-                                        -> *mut gtk_ffi::GtkWidget
-    where T:'static, WC: Fn(&T)->gtk::Widget {
-        let container_gobj: *mut ContainerGObject<T> = item as *mut _;
-        let widget_creator = user_data as *mut WC;
-        unsafe { (*widget_creator)((*container_gobj).get()).to_glib_full() }
+    /* `callback(Some((index, item)))` when a row is selected,
+       `callback(None)` when the selection is cleared. */
+    pub fn connect_item_selected<F>(&self, callback: F) -> SignalHandlerId
+    where F: Fn(Option<(u32, &T)>) + 'static {
+        self.widget.connect_row_selected(move |list_box, row| {
+            panic_guard::guard("item-selected", || {
+                let selected = row.as_ref().and_then(|row| bound_item_at::<T, LM, _>(list_box, row.get_index()));
+                callback(selected.as_ref().map(|&(index, ref item)| (index, item.get())))
+            });
+        })
     }
 
-    extern "C" fn user_data_free_func<WC>(user_data: glib_ffi::gpointer) {
-        let widget_creator = user_data as *mut WC;
-        unsafe { mem::drop(Box::from_raw(widget_creator));}
+    /* Headers above rows, eg: a section title when the first letter changes.
+       `header_func(item, before)` gets the row's item and the one of the row
+       above it, None for the first row, and returns the header, None for no header.
+       It replaces any header function set before. */
+    pub fn set_header_func<F>(&self, header_func: F)
+    where F: Fn(&T, Option<&T>) -> Option<gtk::Widget> + 'static {
+        let refreshing = Rc::new(Cell::new(false));
+        let gio_list_model = self.list_model_gobj.to_gio_list_model();
+        // Connected after gtk's own handler, so the rows are up to date.
+        let list_box = self.widget.downgrade();
+        let refreshing_clone = refreshing.clone();
+        let handler_id = gio_list_model.connect_items_changed(move |_, position, _, added| {
+            let list_box = match list_box.upgrade() {
+                Some(list_box) => list_box,
+                None => return
+            };
+            // The changed rows and the row after them, whose row above changed.
+            refreshing_clone.set(true);
+            for index in position..position + added + 1 {
+                if let Some(row) = list_box.get_row_at_index(index as i32) {
+                    row.changed();
+                }
+            }
+            refreshing_clone.set(false);
+        });
+        let header_func = Box::into_raw(Box::new(HeaderFunc {
+            list_model_gobj: self.list_model_gobj.clone(),
+            list_box: self.widget.downgrade(),
+            header_func,
+            refreshing,
+            handler_id: Some(handler_id)
+        }));
+        unsafe {
+            let list_box_ptr = self.widget.to_glib_none().0;
+            gtk_ffi::gtk_list_box_set_header_func(
+                list_box_ptr,
+                Some(update_header::<T, LM, F>),
+                header_func as glib_ffi::gpointer,
+                Some(free_header_func::<T, LM, F>)
+            );
+            // after the previous header function was freed
+            gobject_ffi::g_object_set_data(list_box_ptr as *mut _, HEADER_FUNC_KEY.as_ptr() as *const _,
+                                           header_func as glib_ffi::gpointer);
+        }
+    }
+}
+
+/* The model position and item of the row or child at `index`,
+   as given by gtk. None for -1, rows not in the widget anymore. */
+fn item_at<T, LM>(list_model_gobj: &ListModelGObject<T, LM>, index: i32)
+                  -> Option<(u32, GObjectPtrWrapper<ContainerGObject<T>>)>
+where T:'static, LM: ListModel<T>+'static {
+    if index < 0 {
+        return None;
+    }
+    list_model_gobj.get_item(index as u32).map(|item| (index as u32, item))
+}
+
+/* item_at() in the model bound to `widget` at the time,
+   so that callbacks don't keep an unbound model alive. */
+fn bound_item_at<T, LM, W>(widget: &W, index: i32) -> Option<(u32, GObjectPtrWrapper<ContainerGObject<T>>)>
+where T:'static, LM: ListModel<T>+'static, W: IsA<glib::Object> {
+    let list_model_gobj = ListModelGObject::<T, LM>::from_object(&get_bound_model(widget)?)?;
+    item_at(&list_model_gobj, index)
+}
+
+impl<T, LM> BoundFlowBoxModel<T, LM> where T:'static, LM: ListModel<T>+'static {
+
+    pub fn get_flow_box(&self) -> &gtk::FlowBox {
+        &self.widget
+    }
+
+    /* `callback(index, item)` when a child is activated, eg: double-clicked.
+       Keeps working after rebinding to a model of the same type. */
+    pub fn connect_item_activated<F>(&self, callback: F) -> SignalHandlerId
+    where F: Fn(u32, &T) + 'static {
+        self.widget.connect_child_activated(move |flow_box, child| {
+            panic_guard::guard("item-activated", || {
+                if let Some((index, item)) = bound_item_at::<T, LM, _>(flow_box, child.get_index()) {
+                    callback(index, item.get());
+                }
+            });
+        })
+    }
+
+    /* `callback(selected)` when the selection changes,
+       with the positions and items of the selected children in model order. */
+    pub fn connect_selected_items_changed<F>(&self, callback: F) -> SignalHandlerId
+    where F: Fn(&[(u32, &T)]) + 'static {
+        self.widget.connect_selected_children_changed(move |flow_box| {
+            panic_guard::guard("selected-items-changed", || {
+                let mut selected = flow_box.get_selected_children().iter()
+                    .filter_map(|child| bound_item_at::<T, LM, _>(flow_box, child.get_index()))
+                    .collect::<Vec<_>>();
+                selected.sort_by_key(|&(index, _)| index);
+                let selected = selected.iter().map(|&(index, ref item)| (index, item.get())).collect::<Vec<_>>();
+                callback(&selected);
+            });
+        })
+    }
+}
+
+/* What gtk_list_box_set_header_func() holds for `BoundListModel::set_header_func()`. */
+struct HeaderFunc<T, LM, F> where T:'static, LM: ListModel<T>+'static {
+    list_model_gobj: GObjectPtrWrapper<ListModelGObject<T, LM>>,
+    list_box: glib::WeakRef<gtk::ListBox>,
+    header_func: F,
+    // true while the rows touched by items-changed are being refreshed
+    refreshing: Rc<Cell<bool>>,
+    handler_id: Option<SignalHandlerId>
+}
+
+unsafe extern "C" fn update_header<T, LM, F>(row: *mut gtk_ffi::GtkListBoxRow,
+                                             before: *mut gtk_ffi::GtkListBoxRow,
+                                             user_data: glib_ffi::gpointer)
+where T:'static, LM: ListModel<T>+'static, F: Fn(&T, Option<&T>) -> Option<gtk::Widget> {
+    let header_func = &*(user_data as *const HeaderFunc<T, LM, F>);
+    // While gtk adds and removes rows for items-changed, the rows after them
+    // don't match the model's positions yet. They are refreshed once it's done.
+    if header_func.list_model_gobj.is_emitting() && !header_func.refreshing.get() {
+        return;
+    }
+    // the items are looked up in the user's model too
+    panic_guard::guard("update_header", || {
+        let row: gtk::ListBoxRow = from_glib_borrow(row);
+        let item = match item_at(&header_func.list_model_gobj, row.get_index()) {
+            Some((_, item)) => item,
+            None => return
+        };
+        let before: Option<gtk::ListBoxRow> = from_glib_borrow(before);
+        let before_item = before.and_then(|before| item_at(&header_func.list_model_gobj, before.get_index()));
+        let header = (header_func.header_func)(item.get(), before_item.as_ref().map(|(_, item)| item.get()));
+        row.set_header(header.as_ref());
+    });
+}
+
+unsafe extern "C" fn free_header_func<T, LM, F>(user_data: glib_ffi::gpointer)
+where T:'static, LM: ListModel<T>+'static {
+    panic_guard::guard("free_header_func", || {
+        let mut header_func = Box::from_raw(user_data as *mut HeaderFunc<T, LM, F>);
+        if let Some(handler_id) = header_func.handler_id.take() {
+            header_func.list_model_gobj.to_gio_list_model().disconnect(handler_id);
+        }
+        if let Some(list_box) = header_func.list_box.upgrade() {
+            let list_box_ptr: *mut gobject_ffi::GObject = list_box.to_glib_none().0;
+            let key = HEADER_FUNC_KEY.as_ptr() as *const _;
+            if gobject_ffi::g_object_get_data(list_box_ptr, key) == user_data {
+                gobject_ffi::g_object_set_data(list_box_ptr, key, ptr::null_mut());
+            }
+        }
+    });
+}
+
+/* Removes the header function of `list_box`
+   if it was set by `BoundListModel::set_header_func()`. */
+fn unset_header_func(list_box: &gtk::ListBox) {
+    unsafe {
+        let list_box_ptr = list_box.to_glib_none().0;
+        if !gobject_ffi::g_object_get_data(list_box_ptr as *mut _, HEADER_FUNC_KEY.as_ptr() as *const _).is_null() {
+            gtk_ffi::gtk_list_box_set_header_func(list_box_ptr, None, ptr::null_mut(), None);
+        }
+    }
+}
+
+extern "C" fn create_widget<T, WC> (item: *mut gobject_ffi::GObject, user_data: glib_ffi::gpointer)
+                                    -> *mut gtk_ffi::GtkWidget
+where T:'static, WC: RowFactory<T>+'static {
+    let container_gobj: *mut ContainerGObject<T> = item as *mut _;
+    let row_factory = unsafe { &*(user_data as *const Rc<WC>) };
+    // An empty row or child stands in when the model gave NULL or the factory panicked.
+    let placeholder = || gtk::Label::new(None).upcast::<gtk::Widget>();
+    if container_gobj.is_null() {
+        return placeholder().to_glib_full();
+    }
+    match panic_guard::guard("create_widget", || unsafe { row_factory.create((*container_gobj).get()) }) {
+        Some(widget) => {
+            // The factory outlives gtk's reference when gtk destroys the rows after freeing it.
+            set_row_factory::<T>(&widget, Some(row_factory.clone()));
+            widget.connect_destroy(|widget| {
+                if let Some(row_factory) = unsafe { get_row_factory::<T>(widget) } {
+                    panic_guard::guard("teardown", || row_factory.teardown(widget));
+                }
+                set_row_factory::<T>(widget, None);
+            });
+            widget.to_glib_full()
+        }
+        None => placeholder().to_glib_full()
+    }
+}
+
+// under which a widget made by create_widget keeps its RowFactory
+const ROW_FACTORY_KEY: &[u8] = b"gtk-rs-list-box-model-row-factory\0";
+
+fn set_row_factory<T>(widget: &gtk::Widget, row_factory: Option<Rc<dyn RowFactory<T>>>)
+where T:'static {
+    extern "C" fn free_row_factory<T>(row_factory: glib_ffi::gpointer) where T:'static {
+        panic_guard::guard("free_row_factory", || unsafe {
+            mem::drop(Box::from_raw(row_factory as *mut Rc<dyn RowFactory<T>>));
+        });
     }
     unsafe {
+        let widget_ptr: *mut gtk_ffi::GtkWidget = widget.to_glib_none().0;
+        match row_factory {
+            Some(row_factory) => gobject_ffi::g_object_set_data_full(
+                widget_ptr as *mut _,
+                ROW_FACTORY_KEY.as_ptr() as *const _,
+                Box::into_raw(Box::new(row_factory)) as glib_ffi::gpointer,
+                Some(free_row_factory::<T>)
+            ),
+            None => gobject_ffi::g_object_set_data(widget_ptr as *mut _, ROW_FACTORY_KEY.as_ptr() as *const _, ptr::null_mut())
+        }
+    }
+}
+
+/* The RowFactory which made `widget`, None if it wasn't made by one.
+   Unsafe because the caller must know that it was made for items of T,
+   eg: because it is a row of a model of T which is still bound. */
+unsafe fn get_row_factory<T>(widget: &gtk::Widget) -> Option<Rc<dyn RowFactory<T>>>
+where T:'static {
+    let widget_ptr: *mut gtk_ffi::GtkWidget = widget.to_glib_none().0;
+    let row_factory = gobject_ffi::g_object_get_data(widget_ptr as *mut _, ROW_FACTORY_KEY.as_ptr() as *const _)
+        as *const Rc<dyn RowFactory<T>>;
+    if row_factory.is_null() {
+        None
+    } else {
+        Some((*row_factory).clone())
+    }
+}
+
+/* Whether `list_model_gobj` is the model `widget` shows. */
+fn is_bound_to<W, T, LM>(widget: &W, list_model_gobj: &ListModelGObject<T, LM>) -> bool
+where W: IsA<glib::Object>, T:'static, LM: ListModel<T>+'static {
+    get_bound_model(widget).is_some_and(|bound_model| {
+        let bound_model_ptr: *mut gio_ffi::GListModel = bound_model.to_glib_none().0;
+        ptr::eq(bound_model_ptr as *const ListModelGObject<T, LM>, list_model_gobj)
+    })
+}
+
+/* Updates the row or child of `widget` in place when its item is updated,
+   for as long as `widget` shows `list_model_gobj`. */
+fn connect_row_updates<W, T, LM>(widget: &W, list_model_gobj: &ListModelGObject<T, LM>)
+where W: ModelWidget, T:'static, LM: ListModel<T>+'static {
+    let weak_widget = widget.downgrade();
+    list_model_gobj.connect_item_updated(move |list_model_gobj, position| {
+        let widget = match weak_widget.upgrade() {
+            Some(widget) => widget,
+            None => return true
+        };
+        if !is_bound_to(&widget, list_model_gobj) {
+            return true;
+        }
+        let item_widget = match widget.get_item_widget(position as i32) {
+            Some(item_widget) => item_widget,
+            // no row to update
+            None => return true
+        };
+        // the rows were made for this model's T as long as it is bound
+        if unsafe { update_row(list_model_gobj, item_widget, position) } {
+            widget.item_widget_changed(position as i32);
+            true
+        } else {
+            false
+        }
+    });
+}
+
+/* Updates the widget made for the item at `position` in place,
+   `row` being the ListBoxRow or FlowBoxChild holding it.
+   false if its RowFactory can't, which `row` must have been made with. */
+unsafe fn update_row<T, LM>(list_model_gobj: &ListModelGObject<T, LM>, row: gtk::Widget, position: u32) -> bool
+where T:'static, LM: ListModel<T>+'static {
+    // gtk wraps the widget in a row unless it is one
+    let widget = match get_row_factory::<T>(&row) {
+        Some(_) => row,
+        None => match row.downcast::<gtk::Bin>().ok().and_then(|row| row.get_child()) {
+            Some(widget) => widget,
+            None => return false
+        }
+    };
+    let row_factory = match get_row_factory::<T>(&widget) {
+        Some(row_factory) => row_factory,
+        None => return false
+    };
+    match list_model_gobj.get_item(position) {
+        Some(item) => panic_guard::guard("update", || row_factory.update(&widget, item.get())).unwrap_or(false),
+        None => false
+    }
+}
+
+extern "C" fn user_data_free_func<WC>(user_data: glib_ffi::gpointer) {
+    let row_factory = user_data as *mut Rc<WC>;
+    panic_guard::guard("user_data_free_func", || unsafe { mem::drop(Box::from_raw(row_factory)); });
+}
+
+/* Also available as `ListBoxModelExt::bind_rust_model()`. */
+pub fn gtk_list_box_bind_model<T, LM, F>(list_box: &gtk::ListBox,
+                                         list_model: LM,
+                                         widget_creator: F ) -> BoundListModel<T, LM>
+where T:'static, LM: ListModel<T>+'static, F: Fn(&T) -> gtk::Widget+'static   {
+    gtk_list_box_bind_model_with_factory(list_box, list_model, widget_creator)
+}
+
+/* gtk_list_box_bind_model with a RowFactory, which can also
+   update rows in place and hear about their destruction. */
+pub fn gtk_list_box_bind_model_with_factory<T, LM, WC>(list_box: &gtk::ListBox,
+                                                       list_model: LM,
+                                                       row_factory: WC ) -> BoundListModel<T, LM>
+where T:'static, LM: ListModel<T>+'static, WC: RowFactory<T>+'static   {
+    let list_model_gobj = ListModelGObject::new(list_model);
+    unsafe {
+        // gtk takes its own reference to the model.
         gtk_ffi::gtk_list_box_bind_model(
             list_box.to_glib_none().0,
-            ListModelGObject::new(list_model).to_glib_full() as *mut _,
+            list_model_gobj.0 as *mut _,
             Some(create_widget::<T, WC>),
-            Box::into_raw(Box::from(widget_creator))  as glib_ffi::gpointer,
+            Box::into_raw(Box::new(Rc::new(row_factory))) as glib_ffi::gpointer,
             Some(user_data_free_func::<WC>)
         );
     }
+    set_bound_model(list_box, Some(list_model_gobj.to_object()));
+    connect_row_updates(list_box, &list_model_gobj);
+    BoundModel { widget: list_box.clone(), list_model_gobj }
+}
+
+/* gtk_list_box_bind_model for a gtk::FlowBox, eg: a grid of thumbnails.
+   The same ListModel can feed either widget.
+   Also available as `FlowBoxModelExt::bind_rust_model()`. */
+pub fn gtk_flow_box_bind_model<T, LM, F>(flow_box: &gtk::FlowBox,
+                                         list_model: LM,
+                                         widget_creator: F ) -> BoundFlowBoxModel<T, LM>
+where T:'static, LM: ListModel<T>+'static, F: Fn(&T) -> gtk::Widget+'static   {
+    gtk_flow_box_bind_model_with_factory(flow_box, list_model, widget_creator)
+}
+
+/* gtk_flow_box_bind_model with a RowFactory. */
+pub fn gtk_flow_box_bind_model_with_factory<T, LM, WC>(flow_box: &gtk::FlowBox,
+                                                       list_model: LM,
+                                                       row_factory: WC ) -> BoundFlowBoxModel<T, LM>
+where T:'static, LM: ListModel<T>+'static, WC: RowFactory<T>+'static   {
+    let list_model_gobj = ListModelGObject::new(list_model);
+    unsafe {
+        // gtk takes its own reference to the model.
+        gtk_ffi::gtk_flow_box_bind_model(
+            flow_box.to_glib_none().0,
+            list_model_gobj.0 as *mut _,
+            Some(create_widget::<T, WC>),
+            Box::into_raw(Box::new(Rc::new(row_factory))) as glib_ffi::gpointer,
+            Some(user_data_free_func::<WC>)
+        );
+    }
+    set_bound_model(flow_box, Some(list_model_gobj.to_object()));
+    connect_row_updates(flow_box, &list_model_gobj);
+    BoundModel { widget: flow_box.clone(), list_model_gobj }
+}
+
+// under which a bound widget keeps a reference to its ListModelGObject
+const BOUND_MODEL_KEY: &[u8] = b"gtk-rs-list-box-model\0";
+// under which a list box points to the HeaderFunc it holds, if any
+const HEADER_FUNC_KEY: &[u8] = b"gtk-rs-list-box-model-header-func\0";
+
+/* Remembers the model bound to `widget` for `bound_model()`, None forgets it. */
+fn set_bound_model<W>(widget: &W, list_model_gobj: Option<glib::Object>)
+where W: IsA<glib::Object> {
+    extern "C" fn unref_bound_model(list_model_gobj: glib_ffi::gpointer) {
+        unsafe { gobject_ffi::g_object_unref(list_model_gobj as *mut _); }
+    }
+    unsafe {
+        let gobj_ptr: *mut gobject_ffi::GObject = widget.to_glib_none().0;
+        match list_model_gobj {
+            Some(list_model_gobj) => {
+                let list_model_ptr: *mut gobject_ffi::GObject = list_model_gobj.to_glib_full();
+                gobject_ffi::g_object_set_data_full(
+                    gobj_ptr,
+                    BOUND_MODEL_KEY.as_ptr() as *const _,
+                    list_model_ptr as glib_ffi::gpointer,
+                    Some(unref_bound_model)
+                );
+            }
+            None => gobject_ffi::g_object_set_data(gobj_ptr, BOUND_MODEL_KEY.as_ptr() as *const _, ptr::null_mut())
+        }
+    }
+}
+
+fn get_bound_model<W>(widget: &W) -> Option<GioListModel>
+where W: IsA<glib::Object> {
+    unsafe {
+        let gobj_ptr: *mut gobject_ffi::GObject = widget.to_glib_none().0;
+        let list_model_ptr = gobject_ffi::g_object_get_data(gobj_ptr, BOUND_MODEL_KEY.as_ptr() as *const _);
+        from_glib_none(list_model_ptr as *mut gio_ffi::GListModel)
+    }
+}
+
+/* A clone of the item at `index` of the model bound to `widget`,
+   None if there is none or it isn't a T. */
+fn get_bound_item<W, T>(widget: &W, index: i32) -> Option<T>
+where W: IsA<glib::Object>, T: Clone+'static {
+    if index < 0 {
+        return None;
+    }
+    let item = get_bound_model(widget)?.get_object(index as u32)?;
+    ContainerGObject::<T>::from_object(&item).map(|container_gobj| container_gobj.get().clone())
+}
+
+/* Binding Rust models as methods of gtk::ListBox, like the rest of gtk-rs. */
+pub trait ListBoxModelExt {
+    /* See `gtk_list_box_bind_model()`. */
+    fn bind_rust_model<T, LM, F>(&self, list_model: LM, widget_creator: F) -> BoundListModel<T, LM>
+    where T:'static, LM: ListModel<T>+'static, F: Fn(&T) -> gtk::Widget+'static;
+
+    /* See `gtk_list_box_bind_model_with_factory()`. */
+    fn bind_rust_model_with_factory<T, LM, WC>(&self, list_model: LM, row_factory: WC) -> BoundListModel<T, LM>
+    where T:'static, LM: ListModel<T>+'static, WC: RowFactory<T>+'static;
+
+    /* Removes the rows and lets go of the model, the widget creator
+       and the header function set by `BoundListModel::set_header_func()`. */
+    fn unbind_model(&self);
+
+    /* A handle to the bound model,
+       None if there is none or it isn't an LM of T. */
+    fn bound_model<T, LM>(&self) -> Option<BoundListModel<T, LM>>
+    where T:'static, LM: ListModel<T>+'static;
+
+    /* A clone of the item shown by `row`, None if it doesn't show a T. */
+    fn get_row_item<T>(&self, row: &gtk::ListBoxRow) -> Option<T>
+    where T: Clone+'static;
+}
+
+impl ListBoxModelExt for gtk::ListBox {
+    fn bind_rust_model<T, LM, F>(&self, list_model: LM, widget_creator: F) -> BoundListModel<T, LM>
+    where T:'static, LM: ListModel<T>+'static, F: Fn(&T) -> gtk::Widget+'static {
+        gtk_list_box_bind_model(self, list_model, widget_creator)
+    }
+
+    fn bind_rust_model_with_factory<T, LM, WC>(&self, list_model: LM, row_factory: WC) -> BoundListModel<T, LM>
+    where T:'static, LM: ListModel<T>+'static, WC: RowFactory<T>+'static {
+        gtk_list_box_bind_model_with_factory(self, list_model, row_factory)
+    }
+
+    fn unbind_model(&self) {
+        unset_header_func(self);
+        unsafe {
+            gtk_ffi::gtk_list_box_bind_model(self.to_glib_none().0, ptr::null_mut(), None, ptr::null_mut(), None);
+        }
+        set_bound_model(self, None);
+    }
+
+    fn bound_model<T, LM>(&self) -> Option<BoundListModel<T, LM>>
+    where T:'static, LM: ListModel<T>+'static {
+        let list_model_gobj = ListModelGObject::<T, LM>::from_object(&get_bound_model(self)?)?;
+        Some(BoundModel { widget: self.clone(), list_model_gobj })
+    }
+
+    fn get_row_item<T>(&self, row: &gtk::ListBoxRow) -> Option<T>
+    where T: Clone+'static {
+        get_bound_item(self, row.get_index())
+    }
+}
+
+/* ListBoxModelExt for gtk::FlowBox. */
+pub trait FlowBoxModelExt {
+    /* See `gtk_flow_box_bind_model()`. */
+    fn bind_rust_model<T, LM, F>(&self, list_model: LM, widget_creator: F) -> BoundFlowBoxModel<T, LM>
+    where T:'static, LM: ListModel<T>+'static, F: Fn(&T) -> gtk::Widget+'static;
+
+    /* See `gtk_flow_box_bind_model_with_factory()`. */
+    fn bind_rust_model_with_factory<T, LM, WC>(&self, list_model: LM, row_factory: WC) -> BoundFlowBoxModel<T, LM>
+    where T:'static, LM: ListModel<T>+'static, WC: RowFactory<T>+'static;
+
+    /* Removes the children and lets go of the model and the widget creator. */
+    fn unbind_model(&self);
+
+    fn bound_model<T, LM>(&self) -> Option<BoundFlowBoxModel<T, LM>>
+    where T:'static, LM: ListModel<T>+'static;
+
+    /* A clone of the item shown by `child`, None if it doesn't show a T. */
+    fn get_child_item<T>(&self, child: &gtk::FlowBoxChild) -> Option<T>
+    where T: Clone+'static;
+}
+
+impl FlowBoxModelExt for gtk::FlowBox {
+    fn bind_rust_model<T, LM, F>(&self, list_model: LM, widget_creator: F) -> BoundFlowBoxModel<T, LM>
+    where T:'static, LM: ListModel<T>+'static, F: Fn(&T) -> gtk::Widget+'static {
+        gtk_flow_box_bind_model(self, list_model, widget_creator)
+    }
+
+    fn bind_rust_model_with_factory<T, LM, WC>(&self, list_model: LM, row_factory: WC) -> BoundFlowBoxModel<T, LM>
+    where T:'static, LM: ListModel<T>+'static, WC: RowFactory<T>+'static {
+        gtk_flow_box_bind_model_with_factory(self, list_model, row_factory)
+    }
+
+    fn unbind_model(&self) {
+        unsafe {
+            gtk_ffi::gtk_flow_box_bind_model(self.to_glib_none().0, ptr::null_mut(), None, ptr::null_mut(), None);
+        }
+        set_bound_model(self, None);
+    }
+
+    fn bound_model<T, LM>(&self) -> Option<BoundFlowBoxModel<T, LM>>
+    where T:'static, LM: ListModel<T>+'static {
+        let list_model_gobj = ListModelGObject::<T, LM>::from_object(&get_bound_model(self)?)?;
+        Some(BoundModel { widget: self.clone(), list_model_gobj })
+    }
+
+    fn get_child_item<T>(&self, child: &gtk::FlowBoxChild) -> Option<T>
+    where T: Clone+'static {
+        get_bound_item(self, child.get_index())
+    }
+}
+
+/* For models whose items are shared pointers (Rc<T>, Arc<T>, ...),
+   so that handing out a row costs a pointer clone instead of a deep one.
+   ContainerGObject keeps the pointer, the widget creator gets &T. */
+pub fn gtk_list_box_bind_shared_model<T, P, LM, F>(list_box: &gtk::ListBox,
+                                                   list_model: LM,
+                                                   widget_creator: F ) -> BoundListModel<P, LM>
+where T: ?Sized, P: Deref<Target=T>+'static, LM: ListModel<P>+'static, F: Fn(&T) -> gtk::Widget+'static   {
+    gtk_list_box_bind_shared_model_with_factory(list_box, list_model, widget_creator)
+}
+
+/* gtk_list_box_bind_shared_model with a RowFactory of the pointers' targets. */
+pub fn gtk_list_box_bind_shared_model_with_factory<T, P, LM, WC>(list_box: &gtk::ListBox,
+                                                                 list_model: LM,
+                                                                 row_factory: WC ) -> BoundListModel<P, LM>
+where T: ?Sized, P: Deref<Target=T>+'static, LM: ListModel<P>+'static, WC: RowFactory<T>+'static   {
+    gtk_list_box_bind_model_with_factory(list_box, list_model, SharedRowFactory(row_factory))
+}
+
+/* The RowFactory of the pointers, made of the one of their targets. */
+struct SharedRowFactory<WC>(WC);
+
+impl<P, WC> RowFactory<P> for SharedRowFactory<WC>
+where P: Deref, WC: RowFactory<P::Target> {
+    fn create(&self, item: &P) -> gtk::Widget {
+        self.0.create(&**item)
+    }
+
+    fn update(&self, widget: &gtk::Widget, item: &P) -> bool {
+        self.0.update(widget, &**item)
+    }
+
+    fn teardown(&self, widget: &gtk::Widget) {
+        self.0.teardown(widget)
+    }
+}
+
+/* Like gtk_list_box_bind_model, with rows the user can drag to reorder the model.
+
+   Each row is a ListBoxRow, the drop target, holding an EventBox,
+   the drag source, holding the created widget.
+   A row is highlighted while a drag is over it, and dropping a row on it
+   moves the dragged item to its position with VecListModel::move_item(). */
+pub fn gtk_list_box_bind_reorderable_model<T, F>(list_box: &gtk::ListBox,
+                                                 list_model: VecListModel<T>,
+                                                 widget_creator: F ) -> BoundListModel<T, VecListModel<T>>
+where T: Clone+'static, F: Fn(&T) -> gtk::Widget+'static {
+    gtk_list_box_bind_reorderable_model_with_factory(list_box, list_model, widget_creator)
+}
+
+/* gtk_list_box_bind_reorderable_model with a RowFactory of the widgets inside the rows. */
+pub fn gtk_list_box_bind_reorderable_model_with_factory<T, WC>(list_box: &gtk::ListBox,
+                                                               list_model: VecListModel<T>,
+                                                               row_factory: WC ) -> BoundListModel<T, VecListModel<T>>
+where T: Clone+'static, WC: RowFactory<T>+'static {
+    let reorderable_rows = ReorderableRows { row_factory, list_model: list_model.clone() };
+    gtk_list_box_bind_model_with_factory(list_box, list_model, reorderable_rows)
+}
+
+// the source row index, as text, only within this application
+const ROW_DRAG_TARGET: &str = "GTK_LIST_BOX_ROW";
+
+/* The RowFactory of gtk_list_box_bind_reorderable_model,
+   wrapping the widgets of `row_factory` in draggable rows. */
+struct ReorderableRows<T, WC> {
+    row_factory: WC,
+    list_model: VecListModel<T>
+}
+
+impl<T, WC> ReorderableRows<T, WC> {
+    /* The widget `row_factory` made, inside the row create() made. */
+    fn created_widget(row: &gtk::Widget) -> Option<gtk::Widget> {
+        let handle = row.clone().downcast::<gtk::Bin>().ok()?.get_child()?;
+        handle.downcast::<gtk::Bin>().ok()?.get_child()
+    }
+}
+
+fn parent_list_box(row: &gtk::ListBoxRow) -> Option<gtk::ListBox> {
+    row.get_parent()?.downcast::<gtk::ListBox>().ok()
+}
+
+/* Whether the row being dragged is one of `list_box`,
+   rows of other reorderable list boxes use the same target. */
+fn is_dragged_from(context: &gdk::DragContext, list_box: &gtk::ListBox) -> bool {
+    context.drag_get_source_widget()
+        .and_then(|handle| handle.get_parent())
+        .and_then(|row| row.downcast::<gtk::ListBoxRow>().ok())
+        .and_then(|row| parent_list_box(&row))
+        .is_some_and(|parent| parent == *list_box)
+}
+
+impl<T, WC> RowFactory<T> for ReorderableRows<T, WC>
+where T: Clone+'static, WC: RowFactory<T> {
+    fn create(&self, item: &T) -> gtk::Widget {
+        let widget = self.row_factory.create(item);
+        // gtk only shows the widget it gets, which is now the row
+        widget.show();
+        let handle = gtk::EventBox::new();
+        handle.add(&widget);
+        handle.show();
+        let row = gtk::ListBoxRow::new();
+        row.add(&handle);
+
+        let targets = [gtk::TargetEntry::new(ROW_DRAG_TARGET, gtk::TargetFlags::SAME_APP, 0)];
+        handle.drag_source_set(gdk::ModifierType::BUTTON1_MASK, &targets, gdk::DragAction::MOVE);
+        row.drag_dest_set(gtk::DestDefaults::MOTION | gtk::DestDefaults::DROP, &targets, gdk::DragAction::MOVE);
+
+        handle.connect_drag_data_get(|handle, _, selection_data, _, _| {
+            panic_guard::guard("reorderable row drag-data-get", || {
+                let row = match handle.get_parent().and_then(|parent| parent.downcast::<gtk::ListBoxRow>().ok()) {
+                    Some(row) => row,
+                    None => return
+                };
+                let index = row.get_index().to_string();
+                unsafe {
+                    let selection_data_ptr: *const gtk_ffi::GtkSelectionData = selection_data.to_glib_none().0;
+                    gtk_ffi::gtk_selection_data_set(
+                        selection_data_ptr as *mut _,
+                        gtk_ffi::gtk_selection_data_get_target(selection_data_ptr),
+                        8,
+                        index.as_ptr() as *mut u8,
+                        index.len() as i32
+                    );
+                }
+            });
+        });
+        row.connect_drag_motion(|row, context, _, _, _| {
+            panic_guard::guard("reorderable row drag-motion", || {
+                if let Some(list_box) = parent_list_box(row).filter(|list_box| is_dragged_from(context, list_box)) {
+                    list_box.drag_highlight_row(row);
+                }
+            });
+            Inhibit(false)
+        });
+        row.connect_drag_leave(|row, _, _| {
+            panic_guard::guard("reorderable row drag-leave", || {
+                if let Some(list_box) = parent_list_box(row) {
+                    list_box.drag_unhighlight_row();
+                }
+            });
+        });
+        let reordered_model = self.list_model.clone();
+        row.connect_drag_data_received(move |row, context, _, _, selection_data, _, _| {
+            panic_guard::guard("reorderable row drag-data-received", || {
+                // the index is one of the model bound to this list box only
+                if !parent_list_box(row).is_some_and(|list_box| is_dragged_from(context, &list_box)) {
+                    return;
+                }
+                let from = String::from_utf8(selection_data.get_data_with_length()).ok()
+                    .and_then(|text| text.parse::<u32>().ok());
+                let to = row.get_index();
+                if let Some(from) = from {
+                    if to >= 0 && from < reordered_model.len() {
+                        reordered_model.move_item(from, to as u32);
+                    }
+                }
+            });
+        });
+        row.upcast()
+    }
+
+    fn update(&self, row: &gtk::Widget, item: &T) -> bool {
+        Self::created_widget(row).is_some_and(|widget| self.row_factory.update(&widget, item))
+    }
+
+    fn teardown(&self, row: &gtk::Widget) {
+        if let Some(widget) = Self::created_widget(row) {
+            self.row_factory.teardown(&widget);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -50,18 +972,23 @@ mod test {
     extern crate gtk;
     extern crate glib;
 
+    use std::rc::Rc;
+    use std::cell::RefCell;
     use self::gtk::prelude::*;
+    use super::{ListBoxModelExt, FlowBoxModelExt};
+    use super::super::VecListModel;
+    use super::super::test_helpers::{gtk_test, record_emissions};
 
     struct MyList<T> {
-        items: Vec<T>
+        items: RefCell<Vec<T>>
     }
     impl<T: Clone> super::ListModel<T> for MyList<T> {
         fn get_n_items(&self) -> u32 {
-            self.items.len() as _
+            self.items.borrow().len() as _
         }
 
         fn get_item(&self, index: u32) -> T {
-            self.items[index as usize].clone()
+            self.items.borrow()[index as usize].clone()
         }
     }
 
@@ -70,31 +997,364 @@ mod test {
         label.set_text(s);
         label.upcast()
     }
+
+    /* Labels of the items, which can be updated in place. */
+    struct UpdatableLabels;
+
+    impl super::RowFactory<Rc<RefCell<String>>> for UpdatableLabels {
+        fn create(&self, item: &Rc<RefCell<String>>) -> gtk::Widget {
+            create_widget_fn(&item.borrow())
+        }
+
+        fn update(&self, widget: &gtk::Widget, item: &Rc<RefCell<String>>) -> bool {
+            widget.clone().downcast::<gtk::Label>().unwrap().set_text(&item.borrow());
+            true
+        }
+    }
+
+    /* Text of the Label created for the row at `index`. */
+    fn row_text(list_box: &gtk::ListBox, index: i32) -> Option<String> {
+        let row = list_box.get_row_at_index(index)?;
+        let label = row.get_child()?.downcast::<gtk::Label>().ok()?;
+        label.get_text()
+    }
+
+    #[test]
+    /* Rows of a model handing out Rc<str> are created
+       from the shared str, not from a copy. */
+    fn shared_items_reach_widget_creator() {
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            let shared_item: Rc<str> = Rc::from("Kingdom");
+            let model = VecListModel::from(vec![shared_item.clone()]);
+            let _bound_list = super::gtk_list_box_bind_shared_model(&list_box, model, |s| {
+                let label = gtk::Label::new(Some(s));
+                label.upcast()
+            });
+            assert_eq!(row_text(&list_box, 0), Some(String::from("Kingdom")));
+            // one in the test and one in the model: the weak item cache
+            // doesn't keep the ContainerGObject alive after create_widget
+            assert_eq!(Rc::strong_count(&shared_item), 2);
+        });
+    }
+    #[test]
+    /* Reorderable rows hold the created widget in their drag handle,
+       and moves of the model move the rows. */
+    fn reorderable_rows_hold_created_widgets() {
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            let model = VecListModel::from(vec![String::from("a"), String::from("b"), String::from("c")]);
+            let _bound_list = super::gtk_list_box_bind_reorderable_model(&list_box, model.clone(), create_widget_fn);
+            let handle_text = |index: i32| -> Option<String> {
+                let handle = list_box.get_row_at_index(index)?.get_child()?.downcast::<gtk::EventBox>().ok()?;
+                handle.get_child()?.downcast::<gtk::Label>().ok()?.get_text()
+            };
+            assert_eq!(handle_text(0), Some(String::from("a")));
+            model.move_item(0, 2);
+            let texts: Vec<Option<String>> = (0..3).map(handle_text).collect();
+            assert_eq!(texts, vec![Some(String::from("b")), Some(String::from("c")), Some(String::from("a"))]);
+        });
+    }
+
+    #[test]
+    /* Activated and selected rows come back as their items. */
+    fn item_callbacks_get_items() {
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            let model = VecListModel::from(vec![String::from("a"), String::from("b")]);
+            let bound_list = super::gtk_list_box_bind_model(&list_box, model.clone(), create_widget_fn);
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            let calls_clone = calls.clone();
+            bound_list.connect_item_activated(move |index, item| {
+                calls_clone.borrow_mut().push(format!("activated {} {}", index, item));
+            });
+            let calls_clone = calls.clone();
+            bound_list.connect_item_selected(move |selected| {
+                calls_clone.borrow_mut().push(match selected {
+                    Some((index, item)) => format!("selected {} {}", index, item),
+                    None => String::from("unselected")
+                });
+            });
+
+            let second_row = list_box.get_row_at_index(1).unwrap();
+            second_row.activate();
+            list_box.select_row(&second_row);
+            list_box.unselect_all();
+            assert_eq!(*calls.borrow(), vec!["activated 1 b", "selected 1 b", "unselected"]);
+        });
+    }
+
+    #[test]
+    /* Headers follow the items around changed positions. */
+    fn headers_follow_model_changes() {
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            let model = VecListModel::from(vec![String::from("apple"), String::from("banana")]);
+            let bound_list = super::gtk_list_box_bind_model(&list_box, model.clone(), create_widget_fn);
+            bound_list.set_header_func(|item: &String, before: Option<&String>| {
+                let letter = item.chars().next();
+                if before.and_then(|before| before.chars().next()) == letter {
+                    return None;
+                }
+                letter.map(|letter| gtk::Label::new(Some(&letter.to_string()[..])).upcast())
+            });
+            let headers = || (0..model.len()).map(|index| {
+                list_box.get_row_at_index(index as i32).unwrap().get_header()
+                    .and_then(|header| header.downcast::<gtk::Label>().ok())
+                    .and_then(|label| label.get_text())
+            }).collect::<Vec<_>>();
+            assert_eq!(headers(), vec![Some(String::from("a")), Some(String::from("b"))]);
+
+            model.insert(1, String::from("avocado"));
+            assert_eq!(headers(), vec![Some(String::from("a")), None, Some(String::from("b"))]);
+            model.remove(0);
+            assert_eq!(headers(), vec![Some(String::from("a")), Some(String::from("b"))]);
+            model.insert(1, String::from("cherry"));
+            assert_eq!(headers(), vec![Some(String::from("a")), Some(String::from("c")), Some(String::from("b"))]);
+        });
+    }
+
+    #[test]
+    /* A flow box follows the model and hands back its items. */
+    fn flow_box_binding() {
+        gtk_test(|| {
+            let flow_box = gtk::FlowBox::new();
+            let model = VecListModel::from(vec![String::from("a"), String::from("b")]);
+            let bound_flow_box = super::gtk_flow_box_bind_model(&flow_box, model.clone(), create_widget_fn);
+            model.push(String::from("c"));
+            let child_text = |index| flow_box.get_child_at_index(index).unwrap().get_child()
+                .and_then(|widget| widget.downcast::<gtk::Label>().ok())
+                .and_then(|label| label.get_text());
+            assert_eq!(flow_box.get_children().len(), 3);
+            assert_eq!(child_text(2), Some(String::from("c")));
+
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            let calls_clone = calls.clone();
+            bound_flow_box.connect_item_activated(move |index, item| {
+                calls_clone.borrow_mut().push(format!("activated {} {}", index, item));
+            });
+            let calls_clone = calls.clone();
+            bound_flow_box.connect_selected_items_changed(move |selected| {
+                let items = selected.iter().map(|&(index, item)| format!("{} {}", index, item)).collect::<Vec<_>>();
+                calls_clone.borrow_mut().push(format!("selected [{}]", items.join(", ")));
+            });
+            let child = flow_box.get_child_at_index(1).unwrap();
+            child.activate();
+            flow_box.unselect_all();
+            assert_eq!(*calls.borrow(), vec!["selected [1 b]", "activated 1 b", "selected []"]);
+        });
+    }
+
+    #[test]
+    /* The model bound through the extension traits can be found again from the widget. */
+    fn extension_traits_find_bound_models() {
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            let model = VecListModel::from(vec![String::from("a"), String::from("b")]);
+            list_box.bind_rust_model(model.clone(), create_widget_fn);
+            let row = list_box.get_row_at_index(1).unwrap();
+            assert_eq!(list_box.get_row_item::<String>(&row), Some(String::from("b")));
+            assert_eq!(list_box.get_row_item::<u32>(&row), None);
+            let bound_list = list_box.bound_model::<String, VecListModel<String>>().unwrap();
+            bound_list.model().push(String::from("c"));
+            assert_eq!(list_box.get_children().len(), 3);
+            assert!(list_box.bound_model::<u32, VecListModel<u32>>().is_none());
+
+            list_box.unbind_model();
+            assert_eq!(list_box.get_children().len(), 0);
+            assert!(list_box.bound_model::<String, VecListModel<String>>().is_none());
+
+            let flow_box = gtk::FlowBox::new();
+            flow_box.bind_rust_model(model.clone(), create_widget_fn);
+            let child = flow_box.get_child_at_index(2).unwrap();
+            assert_eq!(flow_box.get_child_item::<String>(&child), Some(String::from("c")));
+            assert!(flow_box.bound_model::<String, VecListModel<String>>().is_some());
+            flow_box.unbind_model();
+            assert_eq!(flow_box.get_children().len(), 0);
+        });
+    }
+
+    #[test]
+    /* After unbind() nothing holds the model or the widget creator anymore. */
+    fn unbind_releases_model_and_widget_creator() {
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            let item = Rc::new(String::from("a"));
+            let widget_creator_data = Rc::new(());
+            let widget_creator_data_clone = widget_creator_data.clone();
+            let bound_list = super::gtk_list_box_bind_shared_model(
+                &list_box, VecListModel::from(vec![item.clone()]), move |text: &String| {
+                    let _ = &widget_creator_data_clone;
+                    create_widget_fn(text)
+                });
+            bound_list.set_header_func(|_: &Rc<String>, _| None);
+            bound_list.connect_item_activated(|_, _| {});
+            assert!(Rc::strong_count(&item) > 1);
+            assert!(bound_list.is_bound());
+
+            bound_list.unbind();
+            assert_eq!(list_box.get_children().len(), 0);
+            assert_eq!(Rc::strong_count(&item), 1);
+            assert_eq!(Rc::strong_count(&widget_creator_data), 1);
+        });
+    }
+
+    #[test]
+    /* Rows with the same keys stay selected across a rebind. */
+    fn rebind_by_key_keeps_selection() {
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            list_box.set_selection_mode(gtk::SelectionMode::Multiple);
+            let strings = |texts: &[&str]| texts.iter().map(|text| String::from(*text)).collect::<Vec<_>>();
+            let bound_list = super::gtk_list_box_bind_model(&list_box, VecListModel::from(strings(&["a", "b", "c"])), create_widget_fn);
+            let old_bound_list = list_box.bound_model::<String, VecListModel<String>>().unwrap();
+            list_box.select_row(&list_box.get_row_at_index(1).unwrap());
+            list_box.select_row(&list_box.get_row_at_index(2).unwrap());
+
+            let new_model = VecListModel::from(strings(&["c", "x", "b"]));
+            let bound_list = bound_list.rebind_by_key(new_model, create_widget_fn, |text: &String| text.clone());
+            let mut selected = list_box.get_selected_rows().iter().map(|row| row.get_index()).collect::<Vec<_>>();
+            selected.sort();
+            assert_eq!(selected, vec![0, 2]);
+            assert_eq!(bound_list.model().len(), 3);
+            // a stale handle doesn't unbind the new model
+            old_bound_list.unbind();
+            assert!(bound_list.is_bound());
+            assert_eq!(list_box.get_children().len(), 3);
+        });
+    }
+
+    #[test]
+    /* Children with the same keys stay selected across a flow box rebind. */
+    fn flow_box_rebind_by_key_keeps_selection() {
+        gtk_test(|| {
+            let flow_box = gtk::FlowBox::new();
+            flow_box.set_selection_mode(gtk::SelectionMode::Multiple);
+            let model = VecListModel::from(vec![String::from("a"), String::from("b")]);
+            let bound_flow_box = super::gtk_flow_box_bind_model(&flow_box, model, create_widget_fn);
+            flow_box.select_child(&flow_box.get_child_at_index(1).unwrap());
+
+            let new_model = VecListModel::from(vec![String::from("b"), String::from("c")]);
+            let bound_flow_box = bound_flow_box.rebind_by_key(new_model, create_widget_fn, |text: &String| text.clone());
+            let selected = flow_box.get_selected_children().iter().map(|child| child.get_index()).collect::<Vec<_>>();
+            assert_eq!(selected, vec![0]);
+            assert!(bound_flow_box.is_bound());
+        });
+    }
+
+    #[test]
+    /* A RowFactory hears about each row it made being destroyed. */
+    fn row_factory_teardown() {
+        struct LabelFactory {
+            torn_down: Rc<RefCell<Vec<String>>>
+        }
+        impl super::RowFactory<String> for LabelFactory {
+            fn create(&self, item: &String) -> gtk::Widget {
+                create_widget_fn(item)
+            }
+            fn teardown(&self, widget: &gtk::Widget) {
+                let label = widget.clone().downcast::<gtk::Label>().unwrap();
+                self.torn_down.borrow_mut().push(label.get_text().unwrap());
+            }
+        }
+
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            let torn_down = Rc::new(RefCell::new(Vec::new()));
+            let model = VecListModel::from(vec![String::from("a"), String::from("b")]);
+            let bound_list = super::gtk_list_box_bind_model_with_factory(&list_box, model.clone(), LabelFactory { torn_down: torn_down.clone() });
+            model.remove(0);
+            assert_eq!(*torn_down.borrow(), vec!["a"]);
+            bound_list.unbind();
+            assert_eq!(*torn_down.borrow(), vec!["a", "b"]);
+        });
+    }
+
+    #[test]
+    /* item_updated() keeps the row when the factory can update it, and recreates it otherwise. */
+    fn item_updated_keeps_updatable_rows() {
+        gtk_test(|| {
+            let item = Rc::new(RefCell::new(String::from("a")));
+            let list_box = gtk::ListBox::new();
+            let bound_list = super::gtk_list_box_bind_model_with_factory(&list_box, VecListModel::from(vec![item.clone()]), UpdatableLabels);
+            let row = list_box.get_row_at_index(0).unwrap();
+            *item.borrow_mut() = String::from("b");
+            bound_list.item_updated(0);
+            assert_eq!(list_box.get_row_at_index(0), Some(row));
+            assert_eq!(row_text(&list_box, 0), Some(String::from("b")));
+
+            let other_list_box = gtk::ListBox::new();
+            let bound_list = super::gtk_list_box_bind_model(&other_list_box, VecListModel::from(vec![item.clone()]),
+                                                            |item| create_widget_fn(&item.borrow()));
+            let row = other_list_box.get_row_at_index(0).unwrap();
+            *item.borrow_mut() = String::from("c");
+            bound_list.item_updated(0);
+            assert!(other_list_box.get_row_at_index(0) != Some(row));
+            assert_eq!(row_text(&other_list_box, 0), Some(String::from("c")));
+        });
+    }
+
+    #[test]
+    /* An update reaches every list box and flow box bound to the model,
+       and is held back as a replacement while the model is frozen. */
+    fn item_updated_reaches_every_binding() {
+        gtk_test(|| {
+            let item = Rc::new(RefCell::new(String::from("a")));
+            let model = VecListModel::from(vec![item.clone()]);
+            let list_box = gtk::ListBox::new();
+            let bound_list = super::gtk_list_box_bind_model_with_factory(&list_box, model.clone(), UpdatableLabels);
+            let flow_box = gtk::FlowBox::new();
+            let _bound_flow_box = super::gtk_flow_box_bind_model_with_factory(&flow_box, model.clone(), UpdatableLabels);
+            let emissions = record_emissions(&model);
+            let child_text = || flow_box.get_child_at_index(0).unwrap().get_child()
+                .and_then(|widget| widget.downcast::<gtk::Label>().ok())
+                .and_then(|label| label.get_text());
+            let row = list_box.get_row_at_index(0).unwrap();
+            let child = flow_box.get_child_at_index(0).unwrap();
+
+            *item.borrow_mut() = String::from("b");
+            bound_list.item_updated(0);
+            assert_eq!(list_box.get_row_at_index(0), Some(row.clone()));
+            assert_eq!(flow_box.get_child_at_index(0), Some(child));
+            assert_eq!(row_text(&list_box, 0), Some(String::from("b")));
+            assert_eq!(child_text(), Some(String::from("b")));
+
+            model.batch(|_| {
+                *item.borrow_mut() = String::from("c");
+                bound_list.item_updated(0);
+                assert_eq!(row_text(&list_box, 0), Some(String::from("b")));
+            });
+            assert!(list_box.get_row_at_index(0) != Some(row));
+            assert_eq!(row_text(&list_box, 0), Some(String::from("c")));
+            assert_eq!(child_text(), Some(String::from("c")));
+            assert_eq!(*emissions.borrow(), vec![(0, 1, 1), (0, 1, 1)]);
+        });
+    }
+
     #[test]
     fn try_creating_a_list_box() {
-        gtk::init().unwrap();
-        let list_box = gtk::ListBox::new();
-        let my_list = MyList::<String> {
-            items: vec![
-                String::from("Kingdom"),
-                String::from("Phylum"),
-                String::from("Class"),
-                String::from("Order"),
-                String::from("Family"),
-                String::from("Genus"),
-                String::from("Species")
-            ]
-        };
+        gtk_test(|| {
+            let list_box = gtk::ListBox::new();
+            let my_list = MyList::<String> {
+                items: RefCell::new(vec![
+                    String::from("Kingdom"),
+                    String::from("Phylum"),
+                    String::from("Class"),
+                    String::from("Order"),
+                    String::from("Family"),
+                    String::from("Genus"),
+                    String::from("Species")
+                ])
+            };
 
-        super::gtk_list_box_bind_model(&list_box, my_list, create_widget_fn);
+            let bound_list = super::gtk_list_box_bind_model(&list_box, my_list, create_widget_fn);
+            bound_list.model().items.borrow_mut().push(String::from("Subspecies"));
+            bound_list.items_changed(7, 0, 1);
 
-        let window = gtk::Window::new(gtk::WindowType::Toplevel);
-        window.add(&list_box);
-        window.show_all();
-        window.connect_delete_event(|_, _| {
-            gtk::main_quit();
-            Inhibit(false)
+            // no window and gtk::main(), they would block the other gtk tests
+            assert_eq!(list_box.get_children().len(), 8);
+            assert_eq!(row_text(&list_box, 0), Some(String::from("Kingdom")));
+            assert_eq!(row_text(&list_box, 7), Some(String::from("Subspecies")));
         });
-        gtk::main();
     }
 }
\ No newline at end of file
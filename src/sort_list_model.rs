@@ -0,0 +1,258 @@
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use super::public_interface::ListModel;
+use super::change_notifier::{ChangeNotifier, HandlerId};
+
+/* Presents the items of a child model in the order given by a comparator.
+
+   GtkListBox ignores its sort func once a model is bound,
+   so sorting has to happen here, before ListModelGObject.
+
+   Equal items keep their order in the child.
+   Items added to a notifying child are put in place by binary search,
+   without sorting the rest again. Clones share the same state. */
+pub struct SortListModel<T, LM>
+where LM: ListModel<T> {
+    inner: Rc<SortInner<T, LM>>
+}
+
+type Comparator<T> = Box<dyn Fn(&T, &T) -> Ordering>;
+
+struct SortInner<T, LM>
+where LM: ListModel<T> {
+    child: LM,
+    comparator: RefCell<Comparator<T>>,
+    // child index of the item at each sorted position
+    order: RefCell<Vec<u32>>,
+    change_notifier: ChangeNotifier,
+    child_handler_id: Cell<Option<HandlerId>>
+}
+
+impl<T, LM> SortListModel<T, LM>
+where T: 'static, LM: ListModel<T> + 'static {
+
+    pub fn new<C>(child: LM, comparator: C) -> Self
+    where C: Fn(&T, &T) -> Ordering + 'static {
+        let inner = Rc::new(SortInner {
+            child,
+            comparator: RefCell::new(Box::new(comparator)),
+            order: RefCell::new(Vec::new()),
+            change_notifier: ChangeNotifier::new(),
+            child_handler_id: Cell::new(None)
+        });
+        let order = inner.sorted_order();
+        *inner.order.borrow_mut() = order;
+
+        if let Some(child_notifier) = inner.child.get_change_notifier() {
+            let weak_inner = Rc::downgrade(&inner);
+            let handler_id = child_notifier.connect(move |position, removed, added| {
+                if let Some(inner) = weak_inner.upgrade() {
+                    inner.child_items_changed(position, removed, added);
+                }
+            });
+            inner.child_handler_id.set(Some(handler_id));
+        }
+        SortListModel { inner }
+    }
+
+    pub fn get_child(&self) -> &LM {
+        &self.inner.child
+    }
+
+    pub fn set_comparator<C>(&self, comparator: C)
+    where C: Fn(&T, &T) -> Ordering + 'static {
+        *self.inner.comparator.borrow_mut() = Box::new(comparator);
+        self.resort();
+    }
+
+    /* Sorts again, eg: when the outcome of the comparator changed.
+       Only the span between the first and the last item
+       that moved is reported as changed. */
+    pub fn resort(&self) {
+        let new_order = self.inner.sorted_order();
+        let changed_span = {
+            let mut order = self.inner.order.borrow_mut();
+            let first = order.iter().zip(new_order.iter()).position(|(old, new)| old != new);
+            let last = order.iter().zip(new_order.iter()).rposition(|(old, new)| old != new);
+            *order = new_order;
+            match (first, last) {
+                (Some(first), Some(last)) => Some((first as u32, (last - first + 1) as u32)),
+                _ => None
+            }
+        };
+        if let Some((position, length)) = changed_span {
+            self.inner.change_notifier.emit(position, length, length);
+        }
+    }
+}
+
+impl<T, LM> SortInner<T, LM>
+where LM: ListModel<T> {
+
+    fn compare(&self, a: &(u32, T), b: &(u32, T)) -> Ordering {
+        // ties are broken by the position in the child
+        (self.comparator.borrow())(&a.1, &b.1).then(a.0.cmp(&b.0))
+    }
+
+    fn sorted_order(&self) -> Vec<u32> {
+        let mut items: Vec<(u32, T)> = (0..self.child.get_n_items())
+            .filter_map(|index| self.child.try_get_item(index).map(|item| (index, item)))
+            .collect();
+        items.sort_by(|a, b| self.compare(a, b));
+        items.into_iter().map(|(index, _)| index).collect()
+    }
+
+    /* Sorted position for the child item at `child_index`. */
+    fn insertion_position(&self, order: &[u32], child_index: u32, item: T) -> usize {
+        let new_item = (child_index, item);
+        let (mut low, mut high) = (0, order.len());
+        while low < high {
+            let middle = (low + high) / 2;
+            let is_before = match self.child.try_get_item(order[middle]) {
+                Some(middle_item) => self.compare(&(order[middle], middle_item), &new_item) == Ordering::Less,
+                None => true
+            };
+            if is_before {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    fn child_items_changed(&self, position: u32, removed: u32, added: u32) {
+        let removed_end = position + removed;
+
+        /* Sorted positions of the removed items, grouped in runs,
+           each run at its position after the earlier runs are gone. */
+        let removed_runs = {
+            let mut order = self.order.borrow_mut();
+            let mut removed_runs: Vec<(usize, usize)> = Vec::new();
+            let mut kept = 0;
+            for child_index in order.iter_mut() {
+                if *child_index < position {
+                    kept += 1;
+                } else if *child_index >= removed_end {
+                    *child_index = *child_index + added - removed;
+                    kept += 1;
+                } else {
+                    match removed_runs.last_mut() {
+                        Some(&mut (run_position, ref mut run_length)) if run_position == kept => *run_length += 1,
+                        _ => removed_runs.push((kept, 1))
+                    }
+                }
+            }
+            removed_runs
+        };
+        for (run_position, run_length) in removed_runs {
+            self.order.borrow_mut().drain(run_position..run_position + run_length);
+            self.change_notifier.emit(run_position as u32, run_length as u32, 0);
+        }
+
+        for child_index in position..position + added {
+            let item = match self.child.try_get_item(child_index) {
+                Some(item) => item,
+                None => continue
+            };
+            let sorted_position = {
+                let mut order = self.order.borrow_mut();
+                let sorted_position = self.insertion_position(&order, child_index, item);
+                order.insert(sorted_position, child_index);
+                sorted_position
+            };
+            self.change_notifier.emit(sorted_position as u32, 0, 1);
+        }
+    }
+}
+
+impl<T, LM> Drop for SortInner<T, LM>
+where LM: ListModel<T> {
+    fn drop(&mut self) {
+        if let (Some(handler_id), Some(child_notifier)) = (self.child_handler_id.get(), self.child.get_change_notifier()) {
+            child_notifier.disconnect(handler_id);
+        }
+    }
+}
+
+impl<T, LM> Clone for SortListModel<T, LM>
+where LM: ListModel<T> {
+    fn clone(&self) -> Self {
+        SortListModel { inner: self.inner.clone() }
+    }
+}
+
+impl<T, LM> ListModel<T> for SortListModel<T, LM>
+where LM: ListModel<T> {
+    fn get_n_items(&self) -> u32 {
+        self.inner.order.borrow().len() as u32
+    }
+
+    fn get_item(&self, index: u32) -> T {
+        let child_index = self.inner.order.borrow()[index as usize];
+        self.inner.child.get_item(child_index)
+    }
+
+    fn try_get_item(&self, index: u32) -> Option<T> {
+        let child_index = *self.inner.order.borrow().get(index as usize)?;
+        self.inner.child.try_get_item(child_index)
+    }
+
+    fn get_change_notifier(&self) -> Option<&ChangeNotifier> {
+        Some(&self.inner.change_notifier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::SortListModel;
+    use super::super::vec_list_model::VecListModel;
+    use super::super::test_helpers::{record_emissions, list_items as items};
+
+    #[test]
+    /* Equal items keep the order they have in the child. */
+    fn items_are_sorted_stably() {
+        let sort_model = SortListModel::new(
+            VecListModel::from(vec![(3, 'a'), (1, 'b'), (3, 'c'), (2, 'd')]),
+            |a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0)
+        );
+        assert_eq!(items(&sort_model), vec![(1, 'b'), (2, 'd'), (3, 'a'), (3, 'c')]);
+    }
+
+    #[test]
+    /* Added child items land at their sorted position,
+       removed ones are reported where they were. */
+    fn follows_child_changes() {
+        let child = VecListModel::from(vec![50, 10, 30]);
+        let sort_model = SortListModel::new(child.clone(), |a: &i32, b: &i32| a.cmp(b));
+        let emissions = record_emissions(&sort_model);
+
+        child.push(20);
+        child.insert(0, 40);
+        assert_eq!(items(&sort_model), vec![10, 20, 30, 40, 50]);
+        assert_eq!(*emissions.borrow(), vec![(1, 0, 1), (3, 0, 1)]);
+
+        emissions.borrow_mut().clear();
+        // child is [40, 50, 10, 30, 20]
+        child.splice(1..4, vec![35]);
+        assert_eq!(items(&sort_model), vec![20, 35, 40]);
+        assert_eq!(*emissions.borrow(), vec![(0, 1, 0), (1, 1, 0), (2, 1, 0), (1, 0, 1)]);
+    }
+
+    #[test]
+    /* A new comparator resorts in place,
+       reporting the span of items that moved. */
+    fn set_comparator_resorts() {
+        let sort_model = SortListModel::new(VecListModel::from(vec![3, 1, 2, 4]), |a: &i32, b: &i32| a.cmp(b));
+        let emissions = record_emissions(&sort_model);
+        sort_model.set_comparator(|a: &i32, b: &i32| (a % 2).cmp(&(b % 2)).then(a.cmp(b)));
+        assert_eq!(items(&sort_model), vec![2, 4, 1, 3]);
+        assert_eq!(*emissions.borrow(), vec![(0, 4, 4)]);
+
+        emissions.borrow_mut().clear();
+        sort_model.resort();
+        assert!(emissions.borrow().is_empty());
+    }
+}
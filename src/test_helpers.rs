@@ -19,4 +19,59 @@ impl Clone for RefCountTestDouble {
             ref_count: self.ref_count
         }
     }
-}
\ No newline at end of file
+}
+
+/* Records every (position, removed, added) the model's ChangeNotifier emits. */
+#[cfg(test)]
+pub fn record_emissions<T, LM>(model: &LM) -> ::std::rc::Rc<::std::cell::RefCell<Vec<(u32, u32, u32)>>>
+where LM: super::ListModel<T> {
+    let emissions = ::std::rc::Rc::new(::std::cell::RefCell::new(Vec::new()));
+    let emissions_clone = emissions.clone();
+    model.get_change_notifier().unwrap().connect(move |position, removed, added| {
+        emissions_clone.borrow_mut().push((position, removed, added));
+    });
+    emissions
+}
+
+/* All the items of the model, in order. */
+#[cfg(test)]
+pub fn list_items<T, LM>(model: &LM) -> Vec<T>
+where LM: super::ListModel<T> {
+    (0..model.get_n_items()).map(|index| model.get_item(index)).collect()
+}
+
+#[cfg(test)]
+type GtkTest = Box<dyn FnOnce() + Send>;
+#[cfg(test)]
+type GtkTestResult = ::std::thread::Result<()>;
+
+/* gtk may only be used from the thread that initialised it,
+   while the test harness runs each test on a thread of its own.
+   Runs `test` on one gtk thread shared by all the tests, one at a time,
+   and fails the calling test with the panic of `test`.
+   `test` must not run gtk::main(), the tests queued after it would wait forever. */
+#[cfg(test)]
+pub fn gtk_test<F>(test: F)
+where F: FnOnce() + Send + 'static {
+    extern crate gtk;
+    use std::sync::OnceLock;
+    use std::sync::mpsc::{channel, Sender};
+    use std::panic::{self, AssertUnwindSafe};
+
+    static GTK_THREAD: OnceLock<Sender<(GtkTest, Sender<GtkTestResult>)>> = OnceLock::new();
+    let gtk_thread = GTK_THREAD.get_or_init(|| {
+        let (sender, receiver) = channel::<(GtkTest, Sender<GtkTestResult>)>();
+        ::std::thread::spawn(move || {
+            gtk::init().unwrap();
+            for (test, result_sender) in receiver {
+                let _ = result_sender.send(panic::catch_unwind(AssertUnwindSafe(test)));
+            }
+        });
+        sender
+    });
+    let (result_sender, result_receiver) = channel();
+    gtk_thread.send((Box::new(test), result_sender)).unwrap();
+    if let Err(panic) = result_receiver.recv().unwrap() {
+        panic::resume_unwind(panic);
+    }
+}
@@ -0,0 +1,391 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::mem;
+use super::public_interface::ListModel;
+use super::change_notifier::ChangeNotifier;
+use super::diff;
+
+/* A ListModel backed by a Vec, which can be mutated after binding.
+
+   Every mutation tells the bound ListModelGObject
+   (and thus gtk) about the smallest range of items it touched.
+
+   Like gtk objects, clones refer to the same list,
+   so one clone can be bound while another one is kept around to mutate it. */
+pub struct VecListModel<T> {
+    items: Rc<RefCell<Vec<T>>>,
+    change_notifier: ChangeNotifier
+}
+
+impl<T> VecListModel<T> {
+
+    pub fn new() -> Self {
+        Self::from(Vec::new())
+    }
+
+    pub fn len(&self) -> u32 {
+        self.items.borrow().len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.borrow().is_empty()
+    }
+
+    pub fn push(&self, item: T) {
+        let position = {
+            let mut items = self.items.borrow_mut();
+            items.push(item);
+            items.len() as u32 - 1
+        };
+        self.change_notifier.emit(position, 0, 1);
+    }
+
+    pub fn insert(&self, index: u32, item: T) {
+        self.items.borrow_mut().insert(index as usize, item);
+        self.change_notifier.emit(index, 0, 1);
+    }
+
+    pub fn remove(&self, index: u32) -> T {
+        let item = self.items.borrow_mut().remove(index as usize);
+        self.change_notifier.emit(index, 1, 0);
+        item
+    }
+
+    /* Moves the item at `from` so that it ends up at `to`,
+       reported as its removal followed by its insertion:
+       only its row is recreated, the rows in between are kept. */
+    pub fn move_item(&self, from: u32, to: u32) {
+        if from == to {
+            return;
+        }
+        // each emission describes the items as they are when it's made
+        let item = self.items.borrow_mut().remove(from as usize);
+        self.change_notifier.emit(from, 1, 0);
+        self.items.borrow_mut().insert(to as usize, item);
+        self.change_notifier.emit(to, 0, 1);
+    }
+
+    /* Replaces the items in `range` with `replace_with`
+       and returns the removed ones. */
+    pub fn splice<I>(&self, range: Range<u32>, replace_with: I) -> Vec<T>
+    where I: IntoIterator<Item=T> {
+        let (removed, added) = {
+            let mut items = self.items.borrow_mut();
+            let len_before = items.len();
+            let removed: Vec<T> = items.splice(range.start as usize..range.end as usize, replace_with).collect();
+            let added = items.len() + removed.len() - len_before;
+            (removed, added as u32)
+        };
+        self.change_notifier.emit(range.start, removed.len() as u32, added);
+        removed
+    }
+
+    pub fn clear(&self) {
+        let removed = mem::take(&mut *self.items.borrow_mut());
+        self.change_notifier.emit(0, removed.len() as u32, 0);
+    }
+
+    /* Removed items are reported run by run, each one as it is removed,
+       so the rows of the kept items are left alone.
+       The last run goes first, so the positions of the others stay valid.
+       `keep` may read the model, it sees the items before any is removed. */
+    pub fn retain<F>(&self, mut keep: F)
+    where F: FnMut(&T) -> bool {
+        let kept: Vec<bool> = self.items.borrow().iter().map(&mut keep).collect();
+        // (position, length)
+        let mut removed_runs: Vec<(u32, u32)> = Vec::new();
+        for (index, _) in kept.iter().enumerate().filter(|&(_, kept)| !kept) {
+            match removed_runs.last_mut() {
+                Some(&mut (run_position, ref mut run_length)) if run_position + *run_length == index as u32 => *run_length += 1,
+                _ => removed_runs.push((index as u32, 1))
+            }
+        }
+        for (position, removed) in removed_runs.into_iter().rev() {
+            self.items.borrow_mut().drain(position as usize..(position + removed) as usize);
+            self.change_notifier.emit(position, removed, 0);
+        }
+    }
+
+    /* A stable sort. Only the span between the first and the last
+       item that moved is reported as changed.
+       `compare` may read the model, it sees the items in their old order. */
+    pub fn sort_by<F>(&self, mut compare: F)
+    where F: FnMut(&T, &T) -> Ordering {
+        let order: Vec<usize> = {
+            let items = self.items.borrow();
+            let mut order: Vec<usize> = (0..items.len()).collect();
+            order.sort_by(|&a, &b| compare(&items[a], &items[b]));
+            order
+        };
+        let changed_span = {
+            let mut items = self.items.borrow_mut();
+            let first = order.iter().enumerate().position(|(index, &old_index)| index != old_index);
+            let last = order.iter().enumerate().rposition(|(index, &old_index)| index != old_index);
+            match (first, last) {
+                (Some(first), Some(last)) => {
+                    let mut old_items: Vec<Option<T>> = items.drain(..).map(Some).collect();
+                    items.extend(order.iter().map(|&old_index| old_items[old_index].take().unwrap()));
+                    Some((first as u32, (last - first + 1) as u32))
+                },
+                _ => None
+            }
+        };
+        if let Some((position, length)) = changed_span {
+            self.change_notifier.emit(position, length, length);
+        }
+    }
+
+    /* Replaces all the items with `new_items`, eg: a fresh snapshot of the backend,
+       reporting only what differs. Items are matched by `key`,
+       matched items which compare unequal are reported as replaced.
+       Rows of the unchanged items, and their focus and selection, are kept. */
+    pub fn set_items<K, KF>(&self, new_items: Vec<T>, key: KF)
+    where K: Eq, KF: Fn(&T) -> K, T: PartialEq {
+        let hunks = {
+            let items = self.items.borrow();
+            let old_keys: Vec<K> = items.iter().map(&key).collect();
+            let new_keys: Vec<K> = new_items.iter().map(&key).collect();
+            let unchanged_pairs: Vec<(usize, usize)> = diff::matching_pairs(&old_keys, &new_keys).into_iter()
+                .filter(|&(old_index, new_index)| items[old_index] == new_items[new_index])
+                .collect();
+            diff::hunks(&unchanged_pairs, items.len(), new_items.len())
+        };
+        let mut new_items: Vec<Option<T>> = new_items.into_iter().map(Some).collect();
+        /* Hunks are applied one at a time, so whoever reads
+           the model during an emission sees the matching state.
+           Items before a hunk are already in their new positions. */
+        for hunk in hunks {
+            let position = hunk.new.start;
+            let removed = hunk.old.end - hunk.old.start;
+            let added = hunk.new.end - hunk.new.start;
+            {
+                let replacement = new_items[hunk.new].iter_mut().map(|item| item.take().unwrap());
+                self.items.borrow_mut().splice(position..position + removed, replacement);
+            }
+            self.change_notifier.emit(position as u32, removed as u32, added as u32);
+        }
+    }
+
+    pub fn extend<I>(&self, new_items: I)
+    where I: IntoIterator<Item=T> {
+        let (position, added) = {
+            let mut items = self.items.borrow_mut();
+            let len_before = items.len();
+            items.extend(new_items);
+            (len_before as u32, (items.len() - len_before) as u32)
+        };
+        self.change_notifier.emit(position, 0, added);
+    }
+
+    /* Runs `f` with change notifications held back,
+       then emits what changed in as few items-changed as possible.
+       gtk doesn't see any of the changes until `f` returns. */
+    pub fn batch<F>(&self, f: F)
+    where F: FnOnce(&Self) {
+        self.change_notifier.batch(|| f(self));
+    }
+
+    pub fn freeze(&self) {
+        self.change_notifier.freeze();
+    }
+
+    pub fn thaw(&self) {
+        self.change_notifier.thaw();
+    }
+}
+
+impl<T> Clone for VecListModel<T> {
+    fn clone(&self) -> Self {
+        VecListModel {
+            items: self.items.clone(),
+            change_notifier: self.change_notifier.clone()
+        }
+    }
+}
+
+impl<T> Default for VecListModel<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<Vec<T>> for VecListModel<T> {
+    fn from(items: Vec<T>) -> Self {
+        VecListModel {
+            items: Rc::new(RefCell::new(items)),
+            change_notifier: ChangeNotifier::new()
+        }
+    }
+}
+
+impl<T: Clone> ListModel<T> for VecListModel<T> {
+    fn get_n_items(&self) -> u32 {
+        self.len()
+    }
+
+    fn get_item(&self, index: u32) -> T {
+        self.items.borrow()[index as usize].clone()
+    }
+
+    fn try_get_item(&self, index: u32) -> Option<T> {
+        self.items.borrow().get(index as usize).cloned()
+    }
+
+    fn get_change_notifier(&self) -> Option<&ChangeNotifier> {
+        Some(&self.change_notifier)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::rc::Rc;
+    use std::cell::{Cell, RefCell};
+    use super::VecListModel;
+    use super::super::public_interface::ListModel;
+    use super::super::test_helpers::{record_emissions, list_items as items};
+
+    #[test]
+    fn push_insert_remove() {
+        let model = VecListModel::from(vec![1, 2, 3]);
+        let emissions = record_emissions(&model);
+        model.push(4);
+        model.insert(0, 0);
+        assert_eq!(model.remove(2), 2);
+        assert_eq!(items(&model), vec![0, 1, 3, 4]);
+        assert_eq!(*emissions.borrow(), vec![(3, 0, 1), (0, 0, 1), (2, 1, 0)]);
+    }
+
+    #[test]
+    fn splice_extend_clear() {
+        let model = VecListModel::from(vec![1, 2, 3, 4]);
+        let emissions = record_emissions(&model);
+        assert_eq!(model.splice(1..3, vec![7, 8, 9]), vec![2, 3]);
+        model.extend(vec![5, 6]);
+        assert_eq!(items(&model), vec![1, 7, 8, 9, 4, 5, 6]);
+        model.clear();
+        model.clear();
+        assert!(model.is_empty());
+        assert_eq!(*emissions.borrow(), vec![(1, 2, 3), (5, 0, 2), (0, 7, 0)]);
+    }
+
+    #[test]
+    /* Plain Rust code hears about changes like gtk does. */
+    fn rust_subscribers() {
+        let model = VecListModel::from(vec!["a"]);
+        let count = Rc::new(Cell::new(model.len()));
+        let (model_clone, count_clone) = (model.clone(), count.clone());
+        let handler_id = model.connect_items_changed(move |_, _, _| count_clone.set(model_clone.len())).unwrap();
+        model.push("b");
+        assert_eq!(count.get(), 2);
+        model.disconnect_items_changed(handler_id);
+        model.push("c");
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn move_item_is_a_removal_and_an_insertion() {
+        let model = VecListModel::from(vec!["a", "b", "c", "d"]);
+        let emissions = record_emissions(&model);
+        model.move_item(0, 2);
+        assert_eq!(items(&model), vec!["b", "c", "a", "d"]);
+        model.move_item(3, 1);
+        assert_eq!(items(&model), vec!["b", "d", "c", "a"]);
+        model.move_item(1, 1);
+        assert_eq!(*emissions.borrow(), vec![(0, 1, 0), (2, 0, 1), (3, 1, 0), (1, 0, 1)]);
+
+        let lengths = Rc::new(RefCell::new(Vec::new()));
+        let (model_clone, lengths_clone) = (model.clone(), lengths.clone());
+        model.get_change_notifier().unwrap().connect(move |_, _, _| lengths_clone.borrow_mut().push(model_clone.len()));
+        model.move_item(0, 3);
+        assert_eq!(*lengths.borrow(), vec![3, 4]);
+    }
+
+    #[test]
+    /* Each run of removed items is reported as it is removed, last run first. */
+    fn retain_reports_removed_runs() {
+        let model = VecListModel::from(vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        let emissions = record_emissions(&model);
+        let lengths = Rc::new(RefCell::new(Vec::new()));
+        let (model_clone, lengths_clone) = (model.clone(), lengths.clone());
+        model.get_change_notifier().unwrap().connect(move |_, _, _| lengths_clone.borrow_mut().push(model_clone.len()));
+        model.retain(|&item| item != 2 && item != 3 && item != 6 && item != 8);
+        assert_eq!(items(&model), vec![1, 4, 5, 7]);
+        assert_eq!(*emissions.borrow(), vec![(7, 1, 0), (5, 1, 0), (1, 2, 0)]);
+        assert_eq!(*lengths.borrow(), vec![7, 6, 4]);
+    }
+
+    #[test]
+    fn sort_by_reports_moved_span() {
+        let model = VecListModel::from(vec![1, 4, 3, 2, 5]);
+        let emissions = record_emissions(&model);
+        model.sort_by(|a, b| a.cmp(b));
+        model.sort_by(|a, b| a.cmp(b));
+        assert_eq!(items(&model), vec![1, 2, 3, 4, 5]);
+        assert_eq!(*emissions.borrow(), vec![(1, 3, 3)]);
+    }
+
+    #[test]
+    /* The closures of retain and sort_by may read the model through a clone. */
+    fn closures_may_read_the_model() {
+        let model = VecListModel::from(vec![3, 1, 2]);
+        let model_clone = model.clone();
+        model.sort_by(|a, b| {
+            assert_eq!(model_clone.len(), 3);
+            a.cmp(b)
+        });
+        model.retain(|&item| item != model_clone.get_item(0));
+        assert_eq!(items(&model), vec![2, 3]);
+    }
+
+    #[test]
+    /* Only the differences between the snapshots are reported,
+       an item with a known key but new contents counts as replaced. */
+    fn set_items_reports_differences() {
+        let model = VecListModel::from(vec![(1, "queued"), (2, "queued"), (3, "running"), (4, "queued")]);
+        let emissions = record_emissions(&model);
+        let snapshot = vec![(0, "queued"), (1, "queued"), (3, "done"), (4, "queued"), (5, "queued")];
+        model.set_items(snapshot.clone(), |job| job.0);
+        assert_eq!(items(&model), snapshot);
+        assert_eq!(*emissions.borrow(), vec![(0, 0, 1), (2, 2, 1), (4, 0, 1)]);
+
+        emissions.borrow_mut().clear();
+        model.set_items(snapshot.clone(), |job| job.0);
+        assert!(emissions.borrow().is_empty());
+    }
+
+    #[test]
+    /* Many mutations, few emissions. */
+    fn batch_merges_emissions() {
+        let model = VecListModel::from((0..10).collect::<Vec<u32>>());
+        let emissions = record_emissions(&model);
+        model.batch(|model| {
+            for item in 100..110 {
+                model.insert(2, item);
+            }
+            model.retain(|&item| item != 7 && item != 8);
+            model.push(200);
+        });
+        assert_eq!(items(&model), vec![0, 1, 109, 108, 107, 106, 105, 104, 103, 102, 101, 100, 2, 3, 4, 5, 6, 9, 200]);
+        assert_eq!(*emissions.borrow(), vec![(2, 0, 10), (17, 2, 0), (18, 0, 1)]);
+    }
+
+    #[test]
+    fn try_get_item_out_of_range() {
+        let model = VecListModel::from(vec![1]);
+        assert_eq!(model.try_get_item(0), Some(1));
+        assert_eq!(model.try_get_item(1), None);
+    }
+
+    #[test]
+    /* Mutations through a clone are seen by the original. */
+    fn clones_share_items() {
+        let model = VecListModel::new();
+        let emissions = record_emissions(&model);
+        model.clone().push(String::from("Hello"));
+        assert_eq!(items(&model), vec![String::from("Hello")]);
+        assert_eq!(*emissions.borrow(), vec![(0, 0, 1)]);
+    }
+}